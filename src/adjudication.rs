@@ -0,0 +1,214 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Side;
+use crate::uci::Score;
+
+/// Adjudicates a loss once the losing engine has scored itself at `-score_cp` or worse and
+/// the winning engine itself at `score_cp` or better, for `move_count` moves each in a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResignRule {
+    pub move_count: u32,
+    pub score_cp: i32,
+}
+
+/// Adjudicates a draw once both engines have kept their scores within `±score_cp` for
+/// `move_count` moves each in a row, counting from move `move_number`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrawRule {
+    pub move_number: u32,
+    pub move_count: u32,
+    pub score_cp: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdjudicationRules {
+    #[serde(default)]
+    pub resign: Option<ResignRule>,
+    #[serde(default)]
+    pub draw: Option<DrawRule>,
+    /// Declares the game drawn once this many plies have been played.
+    #[serde(default)]
+    pub max_plies: Option<u32>,
+}
+
+impl AdjudicationRules {
+    /// Rejects a zero move limit, which would draw every game after its first move. The
+    /// command line cannot give one, but a request body or tournament file can.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_plies == Some(0) {
+            return Err("max_plies must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ResignRule {
+    type Err = String;
+
+    /// Parses the cutechess-style `movecount=N,score=CP`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let fields = parse_fields(input, &["movecount", "score"])?;
+        Ok(ResignRule { move_count: positive(fields[0], "movecount")?, score_cp: positive(fields[1], "score")? as i32 })
+    }
+}
+
+impl FromStr for DrawRule {
+    type Err = String;
+
+    /// Parses the cutechess-style `movenumber=M,movecount=N,score=CP`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let fields = parse_fields(input, &["movenumber", "movecount", "score"])?;
+        Ok(DrawRule {
+            move_number: fields[0],
+            move_count: positive(fields[1], "movecount")?,
+            score_cp: fields[2] as i32,
+        })
+    }
+}
+
+/// The values of `key=value` pairs separated by commas, in the order of `keys`. Every key
+/// must be given exactly once.
+fn parse_fields(input: &str, keys: &[&str]) -> Result<Vec<u32>, String> {
+    let mut values = vec![None; keys.len()];
+    for pair in input.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').ok_or_else(|| format!("expected key=value, got {pair}"))?;
+        let index = keys.iter().position(|known| *known == key).ok_or_else(|| format!("unknown field {key}"))?;
+        if values[index].is_some() {
+            return Err(format!("{key} given twice"));
+        }
+        let value = value.parse::<u32>().map_err(|_| format!("invalid {key}: {value}"))?;
+        values[index] = Some(value);
+    }
+    keys.iter().zip(values).map(|(key, value)| value.ok_or_else(|| format!("missing {key}"))).collect()
+}
+
+fn positive(value: u32, key: &str) -> Result<u32, String> {
+    if value == 0 {
+        return Err(format!("{key} must be greater than zero"));
+    }
+    Ok(value)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Win(Side),
+    Draw,
+}
+
+impl Verdict {
+    /// The PGN result string.
+    pub fn result(self) -> &'static str {
+        match self {
+            Verdict::Win(Side::White) => "1-0",
+            Verdict::Win(Side::Black) => "0-1",
+            Verdict::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// Applies `AdjudicationRules` to the scores of one game, move by move. A rule holds for a
+/// run of plies, so `move_count` moves by both engines are twice as many plies; a move
+/// without a score breaks every run. The runner passes `lowerbound`/`upperbound` scores
+/// as missing.
+#[derive(Debug, Default)]
+pub struct Adjudicator {
+    rules: AdjudicationRules,
+    white_losing: u32,
+    black_losing: u32,
+    drawish: u32,
+}
+
+impl Adjudicator {
+    pub fn new(rules: AdjudicationRules) -> Self {
+        Self { rules, ..Self::default() }
+    }
+
+    /// Counts the score the mover gave for a move played at full move `move_number`, and
+    /// returns a verdict once a rule has held long enough.
+    pub fn record(&mut self, mover: Side, score: Option<Score>, move_number: u32) -> Option<Verdict> {
+        let Some(score) = score else {
+            self.white_losing = 0;
+            self.black_losing = 0;
+            self.drawish = 0;
+            return None;
+        };
+        let cp = score.centipawns();
+        let white_cp = if mover == Side::White { cp } else { -cp };
+
+        if let Some(rule) = self.rules.resign {
+            self.white_losing = if white_cp <= -rule.score_cp { self.white_losing + 1 } else { 0 };
+            self.black_losing = if white_cp >= rule.score_cp { self.black_losing + 1 } else { 0 };
+            let plies = 2 * rule.move_count.max(1);
+            if self.white_losing >= plies {
+                return Some(Verdict::Win(Side::Black));
+            }
+            if self.black_losing >= plies {
+                return Some(Verdict::Win(Side::White));
+            }
+        }
+
+        if let Some(rule) = self.rules.draw {
+            let counts = move_number >= rule.move_number && white_cp.abs() <= rule.score_cp;
+            self.drawish = if counts { self.drawish + 1 } else { 0 };
+            if self.drawish >= 2 * rule.move_count.max(1) {
+                return Some(Verdict::Draw);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cutechess_style_rules() {
+        let resign: ResignRule = "movecount=3,score=600".parse().expect("parse resign");
+        assert_eq!(resign, ResignRule { move_count: 3, score_cp: 600 });
+
+        let draw: DrawRule = "movenumber=40, movecount=8, score=10".parse().expect("parse draw");
+        assert_eq!(draw, DrawRule { move_number: 40, move_count: 8, score_cp: 10 });
+
+        assert!("movecount=3".parse::<ResignRule>().is_err());
+        assert!("movecount=0,score=600".parse::<ResignRule>().is_err());
+        assert!("movecount=3,score=600,depth=2".parse::<ResignRule>().is_err());
+    }
+
+    #[test]
+    fn resigns_only_when_both_engines_agree() {
+        let rules = AdjudicationRules {
+            resign: Some(ResignRule { move_count: 2, score_cp: 500 }),
+            ..AdjudicationRules::default()
+        };
+        let mut adjudicator = Adjudicator::new(rules);
+
+        // White thinks it is winning, black disagrees: no run starts.
+        assert_eq!(adjudicator.record(Side::White, Some(Score::Cp(700)), 20), None);
+        assert_eq!(adjudicator.record(Side::Black, Some(Score::Cp(100)), 20), None);
+
+        assert_eq!(adjudicator.record(Side::White, Some(Score::Cp(700)), 21), None);
+        assert_eq!(adjudicator.record(Side::Black, Some(Score::Cp(-650)), 21), None);
+        assert_eq!(adjudicator.record(Side::White, Some(Score::Mate(5)), 22), None);
+        assert_eq!(adjudicator.record(Side::Black, Some(Score::Cp(-800)), 22), Some(Verdict::Win(Side::White)));
+    }
+
+    #[test]
+    fn draws_after_the_move_number_and_resets_on_missing_scores() {
+        let rules = AdjudicationRules {
+            draw: Some(DrawRule { move_number: 30, move_count: 1, score_cp: 10 }),
+            ..AdjudicationRules::default()
+        };
+        let mut adjudicator = Adjudicator::new(rules);
+
+        assert_eq!(adjudicator.record(Side::White, Some(Score::Cp(0)), 29), None);
+        assert_eq!(adjudicator.record(Side::Black, Some(Score::Cp(0)), 29), None);
+        assert_eq!(adjudicator.record(Side::White, Some(Score::Cp(5)), 30), None);
+        assert_eq!(adjudicator.record(Side::Black, None, 30), None);
+        assert_eq!(adjudicator.record(Side::White, Some(Score::Cp(-5)), 31), None);
+        assert_eq!(adjudicator.record(Side::Black, Some(Score::Cp(8)), 31), Some(Verdict::Draw));
+    }
+}
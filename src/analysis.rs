@@ -0,0 +1,198 @@
+use serde::Serialize;
+use shakmaty::fen::Fen;
+use shakmaty::san::{San, SanPlus};
+use shakmaty::uci::UciMove;
+use shakmaty::{Chess, Color, EnPassantMode, Position};
+
+use crate::bench::BenchLimit;
+use crate::domain::Side;
+use crate::engine::EngineSpec;
+use crate::match_runner::start_position;
+use crate::protocol::spawn_spec;
+use crate::transcript::Transcript;
+use crate::uci::{PvLines, Score, SearchInfo};
+
+pub const DEFAULT_MOVETIME_MS: u64 = 1000;
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// A position to analyse: a start FEN plus the UCI moves played from it.
+pub struct Analysis {
+    pub engine: EngineSpec,
+    pub start_fen: String,
+    pub moves: Vec<String>,
+    pub position: Chess,
+    pub limit: BenchLimit,
+    pub multipv: u32,
+}
+
+/// One candidate line, scored from the point of view of the side to move.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisLine {
+    pub multipv: u32,
+    pub depth: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub pv: Vec<String>,
+    pub san: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisResult {
+    pub fen: String,
+    pub bestmove: String,
+    pub bestmove_san: Option<String>,
+    pub lines: Vec<AnalysisLine>,
+}
+
+impl Analysis {
+    /// Checks the start position and replays `moves` on it. Without a FEN the standard
+    /// start position is used.
+    pub fn new(
+        engine: EngineSpec,
+        fen: Option<&str>,
+        moves: &[String],
+        limit: BenchLimit,
+        multipv: u32,
+    ) -> Result<Self, String> {
+        let start_fen = fen.unwrap_or(START_FEN).trim().to_string();
+        let mut position = start_position(&start_fen)?;
+        for uci in moves {
+            let mv = uci
+                .parse::<UciMove>()
+                .ok()
+                .and_then(|parsed| parsed.to_move(&position).ok())
+                .ok_or_else(|| format!("illegal move {uci}"))?;
+            position.play_unchecked(&mv);
+        }
+
+        Ok(Self { engine, start_fen, moves: moves.to_vec(), position, limit, multipv })
+    }
+
+    pub fn side_to_move(&self) -> Side {
+        match self.position.turn() {
+            Color::White => Side::White,
+            Color::Black => Side::Black,
+        }
+    }
+}
+
+/// Runs one search on the analysed position with a fresh engine process. Every parsed
+/// `info` line is passed to `on_info`; the result holds the last line per `multipv` index.
+pub async fn run_analysis(
+    analysis: &Analysis,
+    mut on_info: impl FnMut(SearchInfo) + Send,
+) -> Result<AnalysisResult, String> {
+    let engine = &analysis.engine;
+    let failed = |err| format!("{}: {err}", engine.id);
+
+    let mut process = spawn_spec(engine, Transcript::default()).await.map_err(failed)?;
+    let info = process.handshake().await.map_err(failed)?;
+    if analysis.multipv > 1 {
+        let Some(option) = info.option("MultiPV") else {
+            process.quit().await;
+            return Err(format!("{} does not support MultiPV", engine.id));
+        };
+        if let Some(max) = option.max
+            && i64::from(analysis.multipv) > max
+        {
+            process.quit().await;
+            return Err(format!("{} supports at most {max} MultiPV lines", engine.id));
+        }
+    }
+    let options = engine.options_with("MultiPV", &analysis.multipv.to_string());
+    process.configure(&options).await.map_err(failed)?;
+    process.new_game(None).await.map_err(failed)?;
+
+    let mut lines = PvLines::default();
+    let limit = analysis.limit;
+    let bestmove = process
+        .search(&analysis.start_fen, &analysis.moves, &limit.search_limits(), limit.timeout_ms(), &mut |info| {
+            lines.update(&info);
+            on_info(info);
+        })
+        .await
+        .map_err(failed)?;
+    process.quit().await;
+
+    let pos = &analysis.position;
+    let lines = lines
+        .top(analysis.multipv as usize)
+        .into_iter()
+        .map(|(multipv, info)| AnalysisLine {
+            multipv,
+            depth: info.depth,
+            score: info.score,
+            nodes: info.nodes,
+            pv: info.pv.clone(),
+            san: pv_to_san(pos, &info.pv),
+        })
+        .collect();
+
+    let bestmove_san = bestmove
+        .parse::<UciMove>()
+        .ok()
+        .and_then(|uci| uci.to_move(pos).ok())
+        .map(|mv| San::from_move(pos, &mv).to_string());
+
+    Ok(AnalysisResult {
+        fen: Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string(),
+        bestmove,
+        bestmove_san,
+        lines,
+    })
+}
+
+/// Converts a UCI principal variation to SAN, stopping at the first move that is not legal.
+pub fn pv_to_san(pos: &Chess, pv: &[String]) -> Vec<String> {
+    let mut pos = pos.clone();
+    let mut sans = Vec::new();
+    for uci in pv {
+        let Some(mv) = uci.parse::<UciMove>().ok().and_then(|parsed| parsed.to_move(&pos).ok()) else {
+            break;
+        };
+        sans.push(SanPlus::from_move_and_play_unchecked(&mut pos, &mv).to_string());
+    }
+    sans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Protocol;
+    use crate::protocol::position_command;
+
+    fn engine() -> EngineSpec {
+        EngineSpec {
+            id: "engine".to_string(),
+            name: "Engine".to_string(),
+            author: String::new(),
+            path: "/opt/engine".into(),
+            args: Vec::new(),
+            working_dir: None,
+            protocol: Protocol::Uci,
+            options: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn replays_moves_from_the_start_position() {
+        let moves = vec!["e2e4".to_string(), "e7e5".to_string()];
+        let analysis = Analysis::new(engine(), None, &moves, BenchLimit::Depth(10), 1).expect("analysis");
+
+        assert_eq!(analysis.side_to_move(), Side::White);
+        assert_eq!(
+            position_command(&analysis.start_fen, &analysis.moves),
+            format!("position fen {START_FEN} moves e2e4 e7e5")
+        );
+
+        let illegal = vec!["e2e5".to_string()];
+        assert!(Analysis::new(engine(), None, &illegal, BenchLimit::Depth(10), 1).is_err());
+    }
+
+    #[test]
+    fn converts_pv_to_san_until_an_illegal_move() {
+        let pos = Chess::default();
+        let pv: Vec<String> = ["f2f3", "e7e5", "g2g4", "d8h4", "a2a3"].iter().map(|m| m.to_string()).collect();
+        assert_eq!(pv_to_san(&pos, &pv), vec!["f3", "e5", "g4", "Qh4#"]);
+    }
+}
@@ -1,6 +1,14 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
-use crate::domain::{Clock, MatchResult, MatchStatus, ResultReason};
+use crate::adjudication::AdjudicationRules;
+use crate::analysis::AnalysisResult;
+use crate::match_runner::DEFAULT_STALL_TIMEOUT_MS;
+use crate::review::DEFAULT_REVIEW_DEPTH;
+use crate::domain::{Clock, CrashReport, Latency, MatchResult, MatchState, MatchStatus, MoveSnapshot, ResultReason, Side, Variant, unix_time_ms};
+use crate::stats::EngineUsage;
+use crate::uci::{Score, SearchInfo};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EnginesResponse {
@@ -19,11 +27,90 @@ pub struct MatchCreateRequest {
     pub white_engine_id: String,
     pub black_engine_id: String,
     pub time_control: TimeControlRequest,
+    /// Review the game with this engine once it finishes.
+    #[serde(default)]
+    pub review: Option<ReviewRequest>,
+    /// Stall watchdog in milliseconds; zero disables it.
+    #[serde(default = "default_stall_timeout_ms")]
+    pub stall_timeout_ms: u64,
+    /// How far past its clock an engine may go before it forfeits.
+    #[serde(default)]
+    pub timemargin_ms: u64,
+    /// Resign and draw rules; both are off unless given.
+    #[serde(default)]
+    pub adjudication: AdjudicationRules,
+    #[serde(default)]
+    pub variant: Variant,
+    /// Chess960 starting position, 0 to 959; a random one when absent.
+    #[serde(default)]
+    pub chess960_index: Option<u32>,
+}
+
+fn default_stall_timeout_ms() -> u64 {
+    DEFAULT_STALL_TIMEOUT_MS
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReviewRequest {
+    pub engine_id: String,
+    #[serde(default = "default_review_depth")]
+    pub depth: u32,
+}
+
+fn default_review_depth() -> u32 {
+    DEFAULT_REVIEW_DEPTH
 }
 
 #[derive(Debug, Deserialize)]
 pub struct TimeControlRequest {
     pub initial_ms: u64,
+    #[serde(default)]
+    pub increment_ms: u64,
+}
+
+/// Runs an EPD test suite. `epd` is the suite text; without a limit each position is
+/// searched for one second.
+#[derive(Debug, Deserialize)]
+pub struct SuiteRequest {
+    pub engine_id: String,
+    pub epd: String,
+    #[serde(default)]
+    pub depth: Option<u32>,
+    #[serde(default)]
+    pub movetime_ms: Option<u64>,
+}
+
+/// Starts a tournament described by a tournament file on the server. Its players are
+/// taken from the server's engines.
+#[derive(Debug, Deserialize)]
+pub struct TournamentRequest {
+    pub config: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TournamentCreateResponse {
+    pub tournament_id: String,
+}
+
+/// Analyses one position: `fen` (the start position if absent) with `moves` played from
+/// it. Without a limit the engine searches for one second.
+#[derive(Debug, Deserialize)]
+pub struct AnalyzeRequest {
+    pub engine_id: String,
+    #[serde(default)]
+    pub fen: Option<String>,
+    #[serde(default)]
+    pub moves: Vec<String>,
+    #[serde(default)]
+    pub depth: Option<u32>,
+    #[serde(default)]
+    pub movetime_ms: Option<u64>,
+    #[serde(default = "default_multipv")]
+    pub multipv: u32,
+}
+
+fn default_multipv() -> u32 {
+    1
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,25 +122,112 @@ pub struct MatchCreateResponse {
 pub struct MatchStatusResponse {
     pub match_id: String,
     pub status: MatchStatus,
+    pub variant: Variant,
+    pub start_fen: String,
     pub current_fen: String,
     pub pgn: String,
+    /// Live clocks: the side to move's search so far is already taken off.
     pub clocks: Clock,
+    /// The side whose clock is ticking, if a search is in progress.
+    pub running: Option<Side>,
     pub result: Option<MatchResult>,
+    /// Exit status and last stderr lines of an engine that crashed.
+    pub crash: Option<CrashReport>,
+    /// Ping round trip of each engine in microseconds, taken off every move's time.
+    pub latency: Latency,
+    /// The side moved by a human over the WebSocket, if any.
+    pub human: Option<Side>,
 }
 
+/// Per-ply arrays for plotting eval and time graphs, plus per-engine aggregates. Index `i`
+/// of every array belongs to ply `ply[i]`; `score` is as reported by the mover and `eval`
+/// is the same score from white's point of view in centipawns.
 #[derive(Debug, Serialize)]
+pub struct MatchStatsResponse {
+    pub match_id: String,
+    pub ply: Vec<u32>,
+    pub side: Vec<Side>,
+    pub score: Vec<Option<Score>>,
+    pub eval: Vec<Option<i32>>,
+    pub depth: Vec<Option<u32>>,
+    pub nodes: Vec<Option<u64>>,
+    pub time_ms: Vec<u64>,
+    pub clock_ms: Vec<u64>,
+    pub overrun_ms: Vec<u64>,
+    pub white: EngineUsage,
+    pub black: EngineUsage,
+}
+
+impl MatchStatsResponse {
+    pub fn new(state: &MatchState) -> Self {
+        let white_first = state.start_fen.split_whitespace().nth(1) != Some("b");
+        let side = |idx: usize| {
+            if idx.is_multiple_of(2) == white_first { Side::White } else { Side::Black }
+        };
+        let moves = &state.moves;
+        let of_side = |wanted: Side| {
+            moves.iter().enumerate().filter(move |(idx, _)| side(*idx) == wanted).map(|(_, mv)| &mv.stats)
+        };
+
+        Self {
+            match_id: state.match_id.clone(),
+            ply: moves.iter().map(|mv| mv.ply).collect(),
+            side: (0..moves.len()).map(side).collect(),
+            score: moves.iter().map(|mv| mv.stats.score).collect(),
+            eval: moves
+                .iter()
+                .enumerate()
+                .map(|(idx, mv)| {
+                    let cp = mv.stats.score?.centipawns();
+                    Some(if side(idx) == Side::White { cp } else { -cp })
+                })
+                .collect(),
+            depth: moves.iter().map(|mv| mv.stats.depth).collect(),
+            nodes: moves.iter().map(|mv| mv.stats.nodes).collect(),
+            time_ms: moves.iter().map(|mv| mv.stats.time_ms).collect(),
+            clock_ms: moves.iter().map(|mv| mv.stats.clock_ms).collect(),
+            overrun_ms: moves.iter().map(|mv| mv.stats.overrun_ms).collect(),
+            white: EngineUsage {
+                latency_us: state.latency.white_us,
+                ..EngineUsage::from_moves(of_side(Side::White), state.time_control.initial_ms)
+            },
+            black: EngineUsage {
+                latency_us: state.latency.black_us,
+                ..EngineUsage::from_moves(of_side(Side::Black), state.time_control.initial_ms)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct MatchStartedEvent {
     pub match_id: String,
+    pub variant: Variant,
     pub start_fen: String,
 }
 
-#[derive(Debug, Serialize)]
+/// Both clocks when they start or stop. `running` is the side whose clock is ticking from
+/// the values given, if any.
+#[derive(Debug, Clone, Serialize)]
 pub struct ClockEvent {
     pub white_ms: u64,
     pub black_ms: u64,
+    pub running: Option<Side>,
 }
 
-#[derive(Debug, Serialize)]
+impl ClockEvent {
+    /// The live clocks of `state` as of now.
+    pub fn live(state: &MatchState) -> Self {
+        let clocks = state.live_clocks(unix_time_ms());
+        Self {
+            white_ms: clocks.white_ms,
+            black_ms: clocks.black_ms,
+            running: state.running.map(|running| running.side),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct MoveEvent {
     pub ply: u32,
     pub uci: String,
@@ -62,8 +236,259 @@ pub struct MoveEvent {
     pub pgn: String,
 }
 
-#[derive(Debug, Serialize)]
+impl From<MoveSnapshot> for MoveEvent {
+    fn from(snapshot: MoveSnapshot) -> Self {
+        Self {
+            ply: snapshot.ply,
+            uci: snapshot.uci,
+            san: snapshot.san,
+            fen: snapshot.fen,
+            pgn: snapshot.pgn,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ResultEvent {
     pub result: String,
     pub reason: ResultReason,
 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InfoEvent {
+    pub side: Side,
+    pub multipv: Option<u32>,
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time_ms: Option<u64>,
+    pub pv: Vec<String>,
+}
+
+impl InfoEvent {
+    pub fn new(side: Side, info: SearchInfo) -> Self {
+        Self {
+            side,
+            multipv: info.multipv,
+            depth: info.depth,
+            seldepth: info.seldepth,
+            score: info.score,
+            nodes: info.nodes,
+            nps: info.nps,
+            time_ms: info.time_ms,
+            pv: info.pv,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEvent {
+    pub message: String,
+}
+
+/// Messages a client may send over the match WebSocket.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlMessage {
+    Abort,
+    Pause,
+    Resume,
+    Move { uci: String },
+}
+
+/// Everything the match runner publishes on a match's event channel.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum MatchEvent {
+    #[serde(rename = "match_started")]
+    Started(MatchStartedEvent),
+    Move(MoveEvent),
+    Clock(ClockEvent),
+    Info(InfoEvent),
+    Result(ResultEvent),
+    Error(ErrorEvent),
+}
+
+impl MatchEvent {
+    /// Event name used on the wire, e.g. the SSE `event:` field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatchEvent::Started(_) => "match_started",
+            MatchEvent::Move(_) => "move",
+            MatchEvent::Clock(_) => "clock",
+            MatchEvent::Info(_) => "info",
+            MatchEvent::Result(_) => "result",
+            MatchEvent::Error(_) => "error",
+        }
+    }
+
+    /// JSON of the event payload alone, without the `event` tag.
+    pub fn data_json(&self) -> String {
+        let data = match self {
+            MatchEvent::Started(event) => serde_json::to_string(event),
+            MatchEvent::Move(event) => serde_json::to_string(event),
+            MatchEvent::Clock(event) => serde_json::to_string(event),
+            MatchEvent::Info(event) => serde_json::to_string(event),
+            MatchEvent::Result(event) => serde_json::to_string(event),
+            MatchEvent::Error(event) => serde_json::to_string(event),
+        };
+        data.unwrap_or_default()
+    }
+}
+
+/// Events streamed while a position is analysed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum AnalysisEvent {
+    Info(InfoEvent),
+    Result(AnalysisResult),
+    Error(ErrorEvent),
+}
+
+impl AnalysisEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnalysisEvent::Info(_) => "info",
+            AnalysisEvent::Result(_) => "result",
+            AnalysisEvent::Error(_) => "error",
+        }
+    }
+
+    pub fn data_json(&self) -> String {
+        let data = match self {
+            AnalysisEvent::Info(event) => serde_json::to_string(event),
+            AnalysisEvent::Result(event) => serde_json::to_string(event),
+            AnalysisEvent::Error(event) => serde_json::to_string(event),
+        };
+        data.unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchCreatedEvent {
+    pub match_id: String,
+    pub white_engine_id: String,
+    pub black_engine_id: String,
+    pub variant: Variant,
+    pub initial_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveSummaryEvent {
+    pub match_id: String,
+    pub ply: u32,
+    pub san: String,
+    pub fen: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchFinishedEvent {
+    pub match_id: String,
+    pub result: String,
+    pub reason: ResultReason,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchErrorEvent {
+    pub match_id: String,
+    pub message: String,
+}
+
+/// An engine that died (`crash`) or stopped responding (`stalled`) and lost its game. The
+/// exit status and stderr are only known for crashes.
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineCrashedEvent {
+    pub match_id: String,
+    pub engine_id: String,
+    pub side: Side,
+    pub reason: ResultReason,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub stderr: Vec<String>,
+}
+
+impl EngineCrashedEvent {
+    pub fn from_crash(match_id: String, reason: ResultReason, crash: CrashReport) -> Self {
+        EngineCrashedEvent {
+            match_id,
+            engine_id: crash.engine_id,
+            side: crash.side,
+            reason,
+            exit_code: crash.exit_code,
+            signal: crash.signal,
+            stderr: crash.stderr,
+        }
+    }
+}
+
+/// A tournament game that finished: game `game` of `games_total` scheduled.
+#[derive(Debug, Clone, Serialize)]
+pub struct TournamentProgressEvent {
+    pub tournament_id: String,
+    pub event: String,
+    pub game: usize,
+    pub games_total: usize,
+    pub white: String,
+    pub black: String,
+    pub result: String,
+    pub reason: Option<ResultReason>,
+}
+
+/// A tournament that ran out of games, was stopped by SPRT, or failed with `error`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TournamentFinishedEvent {
+    pub tournament_id: String,
+    pub event: String,
+    pub games: usize,
+    pub error: Option<String>,
+}
+
+/// Lifecycle events across all matches and tournaments, published on the server-wide
+/// `/api/events` stream.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum ServerEvent {
+    #[serde(rename = "match_created")]
+    Created(MatchCreatedEvent),
+    #[serde(rename = "match_move")]
+    Move(MoveSummaryEvent),
+    #[serde(rename = "match_finished")]
+    Finished(MatchFinishedEvent),
+    #[serde(rename = "match_error")]
+    Error(MatchErrorEvent),
+    #[serde(rename = "engine_crashed")]
+    EngineCrashed(EngineCrashedEvent),
+    #[serde(rename = "tournament_progress")]
+    TournamentProgress(TournamentProgressEvent),
+    #[serde(rename = "tournament_finished")]
+    TournamentFinished(TournamentFinishedEvent),
+}
+
+impl ServerEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::Created(_) => "match_created",
+            ServerEvent::Move(_) => "match_move",
+            ServerEvent::Finished(_) => "match_finished",
+            ServerEvent::Error(_) => "match_error",
+            ServerEvent::EngineCrashed(_) => "engine_crashed",
+            ServerEvent::TournamentProgress(_) => "tournament_progress",
+            ServerEvent::TournamentFinished(_) => "tournament_finished",
+        }
+    }
+
+    pub fn data_json(&self) -> String {
+        let data = match self {
+            ServerEvent::Created(event) => serde_json::to_string(event),
+            ServerEvent::Move(event) => serde_json::to_string(event),
+            ServerEvent::Finished(event) => serde_json::to_string(event),
+            ServerEvent::Error(event) => serde_json::to_string(event),
+            ServerEvent::EngineCrashed(event) => serde_json::to_string(event),
+            ServerEvent::TournamentProgress(event) => serde_json::to_string(event),
+            ServerEvent::TournamentFinished(event) => serde_json::to_string(event),
+        };
+        data.unwrap_or_default()
+    }
+}
@@ -0,0 +1,274 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::domain::Variant;
+use crate::engine::EngineSpec;
+use crate::headless::load_openings;
+use crate::protocol::spawn_spec;
+use crate::transcript::Transcript;
+use crate::uci::SearchLimits;
+
+/// Middlegame and endgame positions from Stockfish's `bench`, plus the start position.
+const BUILTIN_POSITIONS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+];
+
+/// A fixed-depth search may take a while on slow engines; movetime searches get a few
+/// seconds of grace on top of the limit.
+const DEPTH_TIMEOUT_MS: u64 = 300_000;
+const MOVETIME_GRACE_MS: u64 = 5_000;
+
+#[derive(Debug, Clone, Copy)]
+pub enum BenchLimit {
+    Depth(u32),
+    Movetime(u64),
+}
+
+impl BenchLimit {
+    pub fn search_limits(self) -> SearchLimits {
+        match self {
+            BenchLimit::Depth(depth) => SearchLimits {
+                depth: Some(depth),
+                ..SearchLimits::default()
+            },
+            BenchLimit::Movetime(ms) => SearchLimits {
+                movetime: Some(ms),
+                ..SearchLimits::default()
+            },
+        }
+    }
+
+    pub fn timeout_ms(self) -> u64 {
+        match self {
+            BenchLimit::Depth(_) => DEPTH_TIMEOUT_MS,
+            BenchLimit::Movetime(ms) => ms + MOVETIME_GRACE_MS,
+        }
+    }
+}
+
+impl std::fmt::Display for BenchLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BenchLimit::Depth(depth) => write!(f, "depth {depth}"),
+            BenchLimit::Movetime(ms) => write!(f, "movetime {ms} ms"),
+        }
+    }
+}
+
+pub struct BenchOptions {
+    pub limit: BenchLimit,
+    pub runs: u32,
+    pub positions: Option<PathBuf>,
+}
+
+/// The outcome of one search, taken from the last `info` line before `bestmove`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub bestmove: String,
+    pub depth: u32,
+    pub nodes: u64,
+    pub time_ms: u64,
+    /// When the deepest completed depth was first reported.
+    pub time_to_depth_ms: u64,
+}
+
+/// Totals for one engine, averaged over runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchSummary {
+    pub nodes: u64,
+    pub time_ms: u64,
+    pub nps: u64,
+    pub time_to_depth_ms: u64,
+    pub average_depth: f64,
+    /// Positions where every run returned the same bestmove.
+    pub stable_positions: usize,
+    pub positions: usize,
+}
+
+/// Benchmarks every engine in turn and prints per-position results and a summary.
+pub async fn run_bench(engines: &[EngineSpec], options: &BenchOptions) -> Result<Vec<BenchSummary>, String> {
+    let positions = match &options.positions {
+        Some(path) => load_openings(path, Variant::Standard)?,
+        None => BUILTIN_POSITIONS.iter().map(|fen| fen.to_string()).collect(),
+    };
+    if options.runs == 0 {
+        return Err("runs must be at least 1".to_string());
+    }
+
+    let mut summaries = Vec::new();
+    for engine in engines {
+        println!(
+            "Benchmarking {} ({}): {} positions, {}, {} run(s)",
+            engine.id,
+            engine.name,
+            positions.len(),
+            options.limit,
+            options.runs
+        );
+
+        let runs = bench_engine(engine, &positions, options).await?;
+        let summary = summarize(&runs);
+        print_positions(&runs);
+        println!(
+            "  total: nodes {}  time {} ms  nps {}  time-to-depth {} ms  avg depth {:.1}  stable bestmoves {}/{}",
+            summary.nodes,
+            summary.time_ms,
+            summary.nps,
+            summary.time_to_depth_ms,
+            summary.average_depth,
+            summary.stable_positions,
+            summary.positions
+        );
+        summaries.push(summary);
+    }
+
+    if let Some(reference) = summaries.first().filter(|_| summaries.len() > 1) {
+        println!("Speed relative to {}:", engines[0].id);
+        for (engine, summary) in engines.iter().zip(&summaries) {
+            let factor = summary.nps as f64 / reference.nps.max(1) as f64;
+            println!("  {:<20} {:>12} nps  x{factor:.2}", engine.id, summary.nps);
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Searches every position `options.runs` times with one engine process. Results are
+/// indexed by run, then position.
+async fn bench_engine(
+    engine: &EngineSpec,
+    positions: &[String],
+    options: &BenchOptions,
+) -> Result<Vec<Vec<SearchResult>>, String> {
+    let failed = |err| format!("{}: {err}", engine.id);
+
+    let mut process = spawn_spec(engine, Transcript::default()).await.map_err(failed)?;
+    process.handshake().await.map_err(failed)?;
+    process.configure(&engine.options).await.map_err(failed)?;
+    process.is_ready().await.map_err(failed)?;
+
+    let limits = options.limit.search_limits();
+    let mut runs = Vec::new();
+    for _ in 0..options.runs {
+        let mut results = Vec::new();
+        for fen in positions {
+            process.new_game(None).await.map_err(failed)?;
+
+            let started = Instant::now();
+            let mut result = SearchResult {
+                bestmove: String::new(),
+                depth: 0,
+                nodes: 0,
+                time_ms: 0,
+                time_to_depth_ms: 0,
+            };
+            let bestmove = process
+                .search(fen, &[], &limits, options.limit.timeout_ms(), &mut |info| {
+                    let elapsed = info.time_ms.unwrap_or_else(|| started.elapsed().as_millis() as u64);
+                    if let Some(depth) = info.depth
+                        && depth > result.depth
+                    {
+                        result.depth = depth;
+                        result.time_to_depth_ms = elapsed;
+                    }
+                    if let Some(nodes) = info.nodes {
+                        result.nodes = nodes;
+                    }
+                    result.time_ms = elapsed;
+                })
+                .await
+                .map_err(failed)?;
+
+            result.bestmove = bestmove;
+            if result.time_ms == 0 {
+                result.time_ms = started.elapsed().as_millis() as u64;
+            }
+            results.push(result);
+        }
+        runs.push(results);
+    }
+
+    process.quit().await;
+    Ok(runs)
+}
+
+fn print_positions(runs: &[Vec<SearchResult>]) {
+    let Some(first) = runs.first() else {
+        return;
+    };
+    for (idx, result) in first.iter().enumerate() {
+        let agreeing = runs.iter().filter(|run| run[idx].bestmove == result.bestmove).count();
+        println!(
+            "  {:>3}  depth {:>3}  nodes {:>12}  time {:>7} ms  bestmove {} ({agreeing}/{})",
+            idx + 1,
+            result.depth,
+            result.nodes,
+            result.time_ms,
+            result.bestmove,
+            runs.len()
+        );
+    }
+}
+
+pub fn summarize(runs: &[Vec<SearchResult>]) -> BenchSummary {
+    let run_count = runs.len().max(1) as u64;
+    let positions = runs.first().map_or(0, Vec::len);
+    let all = || runs.iter().flatten();
+
+    let nodes: u64 = all().map(|result| result.nodes).sum();
+    let time_ms: u64 = all().map(|result| result.time_ms).sum();
+    let time_to_depth_ms: u64 = all().map(|result| result.time_to_depth_ms).sum();
+    let searches = all().count().max(1);
+    let average_depth = all().map(|result| f64::from(result.depth)).sum::<f64>() / searches as f64;
+
+    let stable_positions = (0..positions)
+        .filter(|&idx| runs.iter().all(|run| run[idx].bestmove == runs[0][idx].bestmove))
+        .count();
+
+    BenchSummary {
+        nodes: nodes / run_count,
+        time_ms: time_ms / run_count,
+        nps: nodes * 1000 / time_ms.max(1),
+        time_to_depth_ms: time_to_depth_ms / run_count,
+        average_depth,
+        stable_positions,
+        positions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(bestmove: &str, nodes: u64, time_ms: u64) -> SearchResult {
+        SearchResult {
+            bestmove: bestmove.to_string(),
+            depth: 10,
+            nodes,
+            time_ms,
+            time_to_depth_ms: time_ms / 2,
+        }
+    }
+
+    #[test]
+    fn summarizes_runs_and_bestmove_stability() {
+        let runs = vec![
+            vec![result("e2e4", 1_000_000, 1000), result("g1f3", 500_000, 500)],
+            vec![result("e2e4", 3_000_000, 1000), result("d2d4", 500_000, 500)],
+        ];
+
+        let summary = summarize(&runs);
+        assert_eq!(summary.nodes, 2_500_000);
+        assert_eq!(summary.time_ms, 1500);
+        assert_eq!(summary.nps, 1_666_666);
+        assert_eq!(summary.time_to_depth_ms, 750);
+        assert_eq!(summary.average_depth, 10.0);
+        assert_eq!(summary.stable_positions, 1);
+        assert_eq!(summary.positions, 2);
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of Chess960 starting positions.
+pub const POSITIONS: u32 = 960;
+
+/// Where the two knights go among the five squares left after the bishops and the queen,
+/// for each remainder of the index divided by 96.
+const KNIGHTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+/// The FEN of Chess960 position `index` in Scharnagl numbering, where 518 is the standard
+/// starting position.
+pub fn start_fen(index: u32) -> Result<String, String> {
+    if index >= POSITIONS {
+        return Err(format!("chess960 index must be below {POSITIONS}, got {index}"));
+    }
+
+    let mut rank = [None; 8];
+    let mut n = index as usize;
+    rank[2 * (n % 4) + 1] = Some('b');
+    n /= 4;
+    rank[2 * (n % 4)] = Some('b');
+    n /= 4;
+    place(&mut rank, n % 6, 'q');
+    n /= 6;
+    let (first, second) = KNIGHTS[n];
+    // Place the second knight first, so the first one's empty-square index still holds.
+    place(&mut rank, second, 'n');
+    place(&mut rank, first, 'n');
+    for piece in ['r', 'k', 'r'] {
+        place(&mut rank, 0, piece);
+    }
+
+    let black: String = rank.iter().map(|piece| piece.expect("every square filled")).collect();
+    let white = black.to_ascii_uppercase();
+    Ok(format!("{black}/pppppppp/8/8/8/8/PPPPPPPP/{white} w KQkq - 0 1"))
+}
+
+/// Puts `piece` on the `nth` empty square of `rank`.
+fn place(rank: &mut [Option<char>; 8], nth: usize, piece: char) {
+    let square = rank.iter_mut().filter(|square| square.is_none()).nth(nth).expect("enough empty squares");
+    *square = Some(piece);
+}
+
+/// A seed for `pair_index`, different on every call.
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    mix(nanos ^ u64::from(std::process::id()))
+}
+
+/// The position index for game pair `pair` in a series drawn from `seed`. Both games of a
+/// pair share it, so every position is played once with each colour.
+pub fn pair_index(seed: u64, pair: usize) -> u32 {
+    (mix(seed.wrapping_add(pair as u64)) % u64::from(POSITIONS)) as u32
+}
+
+/// SplitMix64 finaliser.
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::fen::Fen;
+    use shakmaty::{CastlingMode, Chess};
+
+    use super::*;
+
+    #[test]
+    fn numbers_positions_like_scharnagl() {
+        assert_eq!(start_fen(518).expect("fen"), Fen::default().to_string());
+        assert!(start_fen(0).expect("fen").starts_with("bbqnnrkr/"));
+        assert!(start_fen(959).expect("fen").starts_with("rkrnnqbb/"));
+        assert!(start_fen(POSITIONS).is_err());
+
+        for index in 0..POSITIONS {
+            let fen: Fen = start_fen(index).expect("fen").parse().expect("parse fen");
+            fen.into_position::<Chess>(CastlingMode::Chess960).expect("legal chess960 position");
+        }
+    }
+
+    #[test]
+    fn pairs_draw_stable_indices() {
+        assert_eq!(pair_index(7, 3), pair_index(7, 3));
+        assert!((0..100).all(|pair| pair_index(7, pair) < POSITIONS));
+    }
+}
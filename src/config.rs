@@ -1,6 +1,8 @@
 use serde::Deserialize;
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::human::HUMAN_ID;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct EngineConfigFile {
@@ -14,6 +16,22 @@ pub struct EngineConfig {
     #[serde(default)]
     pub args: Vec<String>,
     pub working_dir: Option<PathBuf>,
+    /// Protocol the engine speaks; UCI unless set to `xboard`.
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// Engine options sent before every game: `setoption` for UCI engines, `option`
+    /// (or `memory`/`cores`) for XBoard engines.
+    #[serde(default)]
+    pub options: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Uci,
+    #[serde(alias = "cecp")]
+    Xboard,
 }
 
 #[derive(Debug)]
@@ -22,6 +40,14 @@ pub enum ConfigError {
     EmptyId,
     EmptyPath,
     DuplicateId(String),
+    /// The id is reserved for the human player of a match.
+    ReservedId(String),
+    InvalidOptionValue { id: String, option: String },
+    MissingBinary { id: String, path: PathBuf },
+    NotExecutable { id: String, path: PathBuf },
+    BadWorkingDir { id: String, path: PathBuf },
+    UnknownOption { id: String, option: String },
+    EngineFailed { id: String, message: String },
 }
 
 impl std::fmt::Display for ConfigError {
@@ -31,6 +57,17 @@ impl std::fmt::Display for ConfigError {
             ConfigError::EmptyId => write!(f, "engine id is empty"),
             ConfigError::EmptyPath => write!(f, "engine path is empty"),
             ConfigError::DuplicateId(id) => write!(f, "duplicate engine id: {id}"),
+            ConfigError::ReservedId(id) => write!(f, "engine id {id} is reserved for human players"),
+            ConfigError::InvalidOptionValue { id, option } => {
+                write!(f, "{id}: option {option} must be a string, number or boolean")
+            }
+            ConfigError::MissingBinary { id, path } => write!(f, "{id}: binary not found: {}", path.display()),
+            ConfigError::NotExecutable { id, path } => write!(f, "{id}: binary is not executable: {}", path.display()),
+            ConfigError::BadWorkingDir { id, path } => {
+                write!(f, "{id}: working_dir is not a directory: {}", path.display())
+            }
+            ConfigError::UnknownOption { id, option } => write!(f, "{id}: engine has no UCI option {option}"),
+            ConfigError::EngineFailed { id, message } => write!(f, "{id}: engine did not start: {message}"),
         }
     }
 }
@@ -41,29 +78,126 @@ impl EngineConfigFile {
     }
 
     pub fn validate(&self) -> Result<(), ConfigError> {
+        match self.problems().into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Every structural problem in the file, in file order.
+    pub fn problems(&self) -> Vec<ConfigError> {
         if self.engine.is_empty() {
-            return Err(ConfigError::EmptyEngineList);
+            return vec![ConfigError::EmptyEngineList];
         }
 
+        let mut problems = Vec::new();
         let mut seen = HashSet::new();
         for entry in &self.engine {
             if entry.id.trim().is_empty() {
-                return Err(ConfigError::EmptyId);
+                problems.push(ConfigError::EmptyId);
             }
 
             if entry.path.as_os_str().is_empty() {
-                return Err(ConfigError::EmptyPath);
+                problems.push(ConfigError::EmptyPath);
             }
 
             if !seen.insert(entry.id.clone()) {
-                return Err(ConfigError::DuplicateId(entry.id.clone()));
+                problems.push(ConfigError::DuplicateId(entry.id.clone()));
+            }
+
+            if entry.id == HUMAN_ID {
+                problems.push(ConfigError::ReservedId(entry.id.clone()));
+            }
+
+            for (option, value) in &entry.options {
+                if option_value(value).is_none() {
+                    problems.push(ConfigError::InvalidOptionValue {
+                        id: entry.id.clone(),
+                        option: option.clone(),
+                    });
+                }
             }
         }
 
-        Ok(())
+        problems
     }
 }
 
+impl EngineConfig {
+    /// Configured UCI options as `setoption` name/value pairs.
+    pub fn option_values(&self) -> Vec<(String, String)> {
+        self.options
+            .iter()
+            .filter_map(|(name, value)| Some((name.clone(), option_value(value)?)))
+            .collect()
+    }
+
+    /// Problems with the files this entry points at: the binary and its working dir.
+    pub fn file_problems(&self) -> Vec<ConfigError> {
+        let mut problems = Vec::new();
+
+        if let Some(dir) = &self.working_dir
+            && !dir.is_dir()
+        {
+            problems.push(ConfigError::BadWorkingDir {
+                id: self.id.clone(),
+                path: dir.clone(),
+            });
+        }
+
+        match resolve_binary(&self.path) {
+            None => problems.push(ConfigError::MissingBinary {
+                id: self.id.clone(),
+                path: self.path.clone(),
+            }),
+            Some(path) if !is_executable(&path) => problems.push(ConfigError::NotExecutable {
+                id: self.id.clone(),
+                path,
+            }),
+            Some(_) => {}
+        }
+
+        problems
+    }
+}
+
+fn option_value(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(value) => Some(value.clone()),
+        toml::Value::Integer(value) => Some(value.to_string()),
+        toml::Value::Float(value) => Some(value.to_string()),
+        toml::Value::Boolean(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Finds the file `Command` would run: the path itself, or for a bare name, the first
+/// match on `PATH`.
+fn resolve_binary(path: &Path) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+    if path.components().count() != 1 {
+        return None;
+    }
+
+    let search = std::env::var_os("PATH")?;
+    std::env::split_paths(&search)
+        .map(|dir| dir.join(path))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().map(|meta| meta.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,6 +229,32 @@ mod tests {
         assert!(config.engine[1].args.is_empty());
     }
 
+    #[test]
+    fn parses_engine_protocol() {
+        let input = r#"
+            [[engine]]
+            id = "stockfish-16"
+            path = "/opt/stockfish"
+
+            [[engine]]
+            id = "crafty"
+            path = "/opt/crafty"
+            protocol = "xboard"
+
+            [[engine]]
+            id = "gnuchess"
+            path = "/usr/games/gnuchess"
+            protocol = "cecp"
+        "#;
+
+        let config = EngineConfigFile::from_str(input).expect("parse config");
+        config.validate().expect("validate config");
+
+        assert_eq!(config.engine[0].protocol, Protocol::Uci);
+        assert_eq!(config.engine[1].protocol, Protocol::Xboard);
+        assert_eq!(config.engine[2].protocol, Protocol::Xboard);
+    }
+
     #[test]
     fn rejects_duplicate_ids() {
         let input = r#"
@@ -129,4 +289,60 @@ mod tests {
 
         matches!(err, ConfigError::EmptyPath);
     }
+
+    #[test]
+    fn parses_uci_options_and_reports_all_problems() {
+        let input = r#"
+            [[engine]]
+            id = "dup"
+            path = "/opt/one"
+
+            [engine.options]
+            Hash = 256
+            Threads = 4
+            Ponder = false
+            SyzygyPath = "/tb"
+
+            [[engine]]
+            id = "dup"
+            path = ""
+
+            [engine.options]
+            Bad = [1, 2]
+        "#;
+
+        let config = EngineConfigFile::from_str(input).expect("parse config");
+        assert_eq!(
+            config.engine[0].option_values(),
+            vec![
+                ("Hash".to_string(), "256".to_string()),
+                ("Ponder".to_string(), "false".to_string()),
+                ("SyzygyPath".to_string(), "/tb".to_string()),
+                ("Threads".to_string(), "4".to_string()),
+            ]
+        );
+
+        let problems = config.problems();
+        assert_eq!(problems.len(), 3);
+        assert!(matches!(problems[0], ConfigError::EmptyPath));
+        assert!(matches!(problems[1], ConfigError::DuplicateId(_)));
+        assert!(matches!(problems[2], ConfigError::InvalidOptionValue { .. }));
+    }
+
+    #[test]
+    fn reports_missing_binary_and_working_dir() {
+        let input = r#"
+            [[engine]]
+            id = "ghost"
+            path = "/definitely/not/here"
+            working_dir = "/definitely/not/a/dir"
+        "#;
+
+        let config = EngineConfigFile::from_str(input).expect("parse config");
+        let problems = config.engine[0].file_problems();
+
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(problems[0], ConfigError::BadWorkingDir { .. }));
+        assert!(matches!(problems[1], ConfigError::MissingBinary { .. }));
+    }
 }
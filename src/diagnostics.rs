@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use tokio::time::timeout;
+
+use crate::config::{ConfigError, EngineConfig, EngineConfigFile};
+use crate::protocol::spawn_engine;
+use crate::transcript::Transcript;
+use crate::uci::{UciEngineInfo, UciError};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What an engine reported during the handshake, and how long it took to answer.
+pub struct ProbeReport {
+    pub info: UciEngineInfo,
+    pub uciok: Duration,
+    pub readyok: Duration,
+}
+
+/// Starts the engine, runs the protocol handshake and a readiness check, and shuts it down
+/// again.
+pub async fn probe_engine(entry: &EngineConfig) -> Result<ProbeReport, UciError> {
+    let working_dir = entry.working_dir.as_ref();
+    let mut process = spawn_engine(entry.protocol, &entry.path, &entry.args, working_dir, Transcript::default()).await?;
+
+    let started = Instant::now();
+    let info = match timeout(PROBE_TIMEOUT, process.handshake()).await {
+        Ok(Ok(info)) => info,
+        Ok(Err(err)) => {
+            process.quit().await;
+            return Err(err);
+        }
+        Err(_) => {
+            process.quit().await;
+            return Err(UciError::Timeout("uciok"));
+        }
+    };
+    let uciok = started.elapsed();
+
+    let started = Instant::now();
+    let ready = timeout(PROBE_TIMEOUT, process.is_ready()).await;
+    let readyok = started.elapsed();
+    process.quit().await;
+    match ready {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => return Err(err),
+        Err(_) => return Err(UciError::Timeout("readyok")),
+    }
+
+    Ok(ProbeReport { info, uciok, readyok })
+}
+
+/// Every problem with a config file: its structure, the files it points at, and options the
+/// engines do not advertise. Engines whose binary is present are started to check options.
+pub async fn check_config(config: &EngineConfigFile) -> Vec<ConfigError> {
+    let mut problems = config.problems();
+
+    for entry in &config.engine {
+        if entry.path.as_os_str().is_empty() {
+            continue;
+        }
+
+        let file_problems = entry.file_problems();
+        if !file_problems.is_empty() {
+            problems.extend(file_problems);
+            continue;
+        }
+
+        let report = match probe_engine(entry).await {
+            Ok(report) => report,
+            Err(err) => {
+                problems.push(ConfigError::EngineFailed {
+                    id: entry.id.clone(),
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
+
+        for option in entry.options.keys() {
+            if report.info.option(option).is_none() {
+                problems.push(ConfigError::UnknownOption {
+                    id: entry.id.clone(),
+                    option: option.clone(),
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+pub fn print_probe(id: &str, report: &ProbeReport) {
+    let info = &report.info;
+    println!("id       {id}");
+    println!("name     {}", info.name);
+    println!("author   {}", info.author);
+    println!("uciok    {:.1} ms", report.uciok.as_secs_f64() * 1000.0);
+    println!("readyok  {:.1} ms", report.readyok.as_secs_f64() * 1000.0);
+
+    if info.options.is_empty() {
+        println!("options  none");
+        return;
+    }
+
+    println!("options");
+    for option in &info.options {
+        let mut line = format!("  {} ({})", option.name, option.kind);
+        if let Some(default) = &option.default {
+            line.push_str(&format!(" default {default}"));
+        }
+        if let (Some(min), Some(max)) = (option.min, option.max) {
+            line.push_str(&format!(" range {min}..{max}"));
+        }
+        if !option.vars.is_empty() {
+            line.push_str(&format!(" choices {}", option.vars.join(", ")));
+        }
+        println!("{line}");
+    }
+}
@@ -1,9 +1,17 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use serde::{Deserialize, Serialize};
+use shakmaty::CastlingMode;
+
+use crate::uci::Score;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MatchStatus {
     Running,
+    Paused,
     Finished,
     Error,
 }
@@ -17,7 +25,120 @@ pub enum ResultReason {
     Illegal,
     Resignation,
     Draw,
+    Aborted,
     Error,
+    /// The engine process exited or was killed during the game.
+    Crash,
+    /// The engine stopped responding during a search.
+    Stalled,
+    /// Ended early by a resign or draw rule on the engines' scores.
+    Adjudication,
+    /// Ended early with the result of the Syzygy tables.
+    Tablebase,
+    /// Drawn on reaching the maximum game length.
+    MoveLimit,
+    /// Decided by a variant's own rule, such as an exploded king, a king on the hill,
+    /// a third check or a king reaching the eighth rank.
+    Variant,
+}
+
+impl fmt::Display for ResultReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResultReason::Checkmate => "checkmate",
+            ResultReason::Stalemate => "stalemate",
+            ResultReason::Timeout => "timeout",
+            ResultReason::Illegal => "illegal",
+            ResultReason::Resignation => "resignation",
+            ResultReason::Draw => "draw",
+            ResultReason::Aborted => "aborted",
+            ResultReason::Error => "error",
+            ResultReason::Crash => "crash",
+            ResultReason::Stalled => "stalled",
+            ResultReason::Adjudication => "adjudication",
+            ResultReason::Tablebase => "tablebase",
+            ResultReason::MoveLimit => "movelimit",
+            ResultReason::Variant => "variant",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The rules a game is played by. Apart from Chess960, the names are those of the
+/// `UCI_Variant` option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Fischer Random: a shuffled back rank, castling with the king moving onto its rook.
+    Chess960,
+    Atomic,
+    Crazyhouse,
+    #[serde(rename = "3check")]
+    ThreeCheck,
+    KingOfTheHill,
+    Antichess,
+    Horde,
+    RacingKings,
+}
+
+impl Variant {
+    pub fn castling_mode(self) -> CastlingMode {
+        match self {
+            Variant::Chess960 => CastlingMode::Chess960,
+            _ => CastlingMode::Standard,
+        }
+    }
+
+    /// The shakmaty rules for positions of this variant.
+    pub fn rules(self) -> shakmaty::variant::Variant {
+        use shakmaty::variant::Variant as Rules;
+        match self {
+            Variant::Standard | Variant::Chess960 => Rules::Chess,
+            Variant::Atomic => Rules::Atomic,
+            Variant::Crazyhouse => Rules::Crazyhouse,
+            Variant::ThreeCheck => Rules::ThreeCheck,
+            Variant::KingOfTheHill => Rules::KingOfTheHill,
+            Variant::Antichess => Rules::Antichess,
+            Variant::Horde => Rules::Horde,
+            Variant::RacingKings => Rules::RacingKings,
+        }
+    }
+
+    /// The PGN `Variant` tag value, as Lichess writes it.
+    pub fn pgn_name(self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::Chess960 => "Chess960",
+            Variant::Atomic => "Atomic",
+            Variant::Crazyhouse => "Crazyhouse",
+            Variant::ThreeCheck => "Three-check",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::Antichess => "Antichess",
+            Variant::Horde => "Horde",
+            Variant::RacingKings => "Racing Kings",
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "standard" | "chess" | "normal" => Ok(Variant::Standard),
+            "chess960" | "fischerandom" | "frc" => Ok(Variant::Chess960),
+            "atomic" => Ok(Variant::Atomic),
+            "crazyhouse" => Ok(Variant::Crazyhouse),
+            "3check" | "threecheck" => Ok(Variant::ThreeCheck),
+            "kingofthehill" | "koth" => Ok(Variant::KingOfTheHill),
+            "antichess" => Ok(Variant::Antichess),
+            "horde" => Ok(Variant::Horde),
+            "racingkings" => Ok(Variant::RacingKings),
+            _ => Err(format!("unknown variant: {input}")),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -27,12 +148,98 @@ pub enum Side {
     Black,
 }
 
+impl Side {
+    /// The side to move in a FEN, read from its second field.
+    pub fn to_move(fen: &str) -> Self {
+        if fen.split_whitespace().nth(1) == Some("b") {
+            Side::Black
+        } else {
+            Side::White
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Clock {
     pub white_ms: u64,
     pub black_ms: u64,
 }
 
+/// The clock of the side whose search is in progress, started at `since_ms` (milliseconds
+/// since the Unix epoch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunningClock {
+    pub side: Side,
+    pub since_ms: u64,
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Sudden-death base time plus a per-move increment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub initial_ms: u64,
+    #[serde(default)]
+    pub increment_ms: u64,
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    /// Parses the cutechess-style `base[+inc]` form, both in seconds, e.g. `10+0.1`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (base, increment) = match input.split_once('+') {
+            Some((base, increment)) => (base, Some(increment)),
+            None => (input, None),
+        };
+
+        let initial_ms = seconds_to_ms(base).ok_or_else(|| format!("invalid base time: {base}"))?;
+        let increment_ms = match increment {
+            Some(increment) => seconds_to_ms(increment).ok_or_else(|| format!("invalid increment: {increment}"))?,
+            None => 0,
+        };
+
+        if initial_ms == 0 {
+            return Err("base time must be greater than zero".to_string());
+        }
+
+        Ok(TimeControl {
+            initial_ms,
+            increment_ms,
+        })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    /// Formats as a PGN `TimeControl` tag value, e.g. `10+0.1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", ms_to_seconds(self.initial_ms))?;
+        if self.increment_ms > 0 {
+            write!(f, "+{}", ms_to_seconds(self.increment_ms))?;
+        }
+        Ok(())
+    }
+}
+
+fn seconds_to_ms(input: &str) -> Option<u64> {
+    let seconds: f64 = input.trim().parse().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+    Some((seconds * 1000.0).round() as u64)
+}
+
+fn ms_to_seconds(ms: u64) -> String {
+    if ms.is_multiple_of(1000) {
+        (ms / 1000).to_string()
+    } else {
+        format!("{}", ms as f64 / 1000.0)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MatchResult {
     pub result: String,
@@ -50,7 +257,91 @@ pub struct MatchState {
     pub side_to_move: Side,
     pub ply: u32,
     pub start_fen: String,
-    pub last_move: Option<MoveSnapshot>,
+    #[serde(default)]
+    pub variant: Variant,
+    pub time_control: TimeControl,
+    pub moves: Vec<MoveSnapshot>,
+    /// Set when the game ended because an engine died.
+    #[serde(default)]
+    pub crash: Option<CrashReport>,
+    #[serde(default)]
+    pub latency: Latency,
+    /// Set while an engine is searching; `clocks` only change once it has moved.
+    #[serde(default)]
+    pub running: Option<RunningClock>,
+    /// The side moved by a human over the match WebSocket, if any.
+    #[serde(default)]
+    pub human: Option<Side>,
+}
+
+impl MatchState {
+    pub fn new(match_id: String, start_fen: String, variant: Variant, time_control: TimeControl) -> Self {
+        let side_to_move = Side::to_move(&start_fen);
+
+        MatchState {
+            match_id,
+            status: MatchStatus::Running,
+            current_fen: start_fen.clone(),
+            pgn: String::new(),
+            clocks: Clock {
+                white_ms: time_control.initial_ms,
+                black_ms: time_control.initial_ms,
+            },
+            result: None,
+            side_to_move,
+            ply: 0,
+            start_fen,
+            variant,
+            time_control,
+            moves: Vec::new(),
+            crash: None,
+            latency: Latency::default(),
+            running: None,
+            human: None,
+        }
+    }
+
+    /// The clocks as of `now_ms`, with the time of the search in progress taken off the
+    /// side to move.
+    pub fn live_clocks(&self, now_ms: u64) -> Clock {
+        let mut clocks = self.clocks.clone();
+        if let Some(running) = self.running {
+            let thinking_ms = now_ms.saturating_sub(running.since_ms);
+            match running.side {
+                Side::White => clocks.white_ms = clocks.white_ms.saturating_sub(thinking_ms),
+                Side::Black => clocks.black_ms = clocks.black_ms.saturating_sub(thinking_ms),
+            }
+        }
+        clocks
+    }
+}
+
+/// How an engine process ended when it died mid-game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub side: Side,
+    pub engine_id: String,
+    pub exit_code: Option<i32>,
+    /// The signal that killed the process, on Unix.
+    pub signal: Option<i32>,
+    /// The last lines the engine wrote to stderr.
+    pub stderr: Vec<String>,
+}
+
+impl fmt::Display for CrashReport {
+    /// `sf-dev (black) exited with code 139` or `sf-dev (black) was killed by signal 11`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = match self.side {
+            Side::White => "white",
+            Side::Black => "black",
+        };
+        write!(f, "{} ({side}) ", self.engine_id)?;
+        match (self.exit_code, self.signal) {
+            (_, Some(signal)) => write!(f, "was killed by signal {signal}"),
+            (Some(code), None) => write!(f, "exited with code {code}"),
+            (None, None) => write!(f, "exited"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,4 +351,102 @@ pub struct MoveSnapshot {
     pub san: String,
     pub fen: String,
     pub pgn: String,
+    pub stats: MoveStats,
+}
+
+/// The search behind a move: the mover's last reported main line, the wall-clock time
+/// the move took and the mover's clock once the increment was added.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveStats {
+    pub score: Option<Score>,
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    /// Time charged for the move: from `go` to the reply, less the engine's latency.
+    pub time_ms: u64,
+    pub clock_ms: u64,
+    /// How far the move went past the clock, forgiven by the time margin.
+    #[serde(default)]
+    pub overrun_ms: u64,
+}
+
+/// Round trip of a ping to each engine, measured before the game and taken off every
+/// move's time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Latency {
+    pub white_us: u64,
+    pub black_us: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_time_control_with_increment() {
+        let tc: TimeControl = "10+0.1".parse().expect("parse tc");
+        assert_eq!(tc.initial_ms, 10_000);
+        assert_eq!(tc.increment_ms, 100);
+        assert_eq!(tc.to_string(), "10+0.1");
+    }
+
+    #[test]
+    fn parses_sudden_death_time_control() {
+        let tc: TimeControl = "60".parse().expect("parse tc");
+        assert_eq!(tc.initial_ms, 60_000);
+        assert_eq!(tc.increment_ms, 0);
+        assert_eq!(tc.to_string(), "60");
+    }
+
+    #[test]
+    fn rejects_bad_time_controls() {
+        assert!("0+1".parse::<TimeControl>().is_err());
+        assert!("abc".parse::<TimeControl>().is_err());
+        assert!("10+x".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn live_clocks_tick_for_the_side_to_move() {
+        let tc = TimeControl {
+            initial_ms: 1_000,
+            increment_ms: 0,
+        };
+        let mut state = MatchState::new("m".to_string(), shakmaty::fen::Fen::default().to_string(), Variant::Standard, tc);
+        assert_eq!(state.live_clocks(5_000).white_ms, 1_000);
+
+        state.running = Some(RunningClock {
+            side: Side::Black,
+            since_ms: 4_700,
+        });
+        let clocks = state.live_clocks(5_000);
+        assert_eq!((clocks.white_ms, clocks.black_ms), (1_000, 700));
+        assert_eq!(state.live_clocks(9_000).black_ms, 0);
+    }
+
+    #[test]
+    fn describes_crashes_by_signal_or_exit_code() {
+        let crash = CrashReport {
+            side: Side::Black,
+            engine_id: "sf-dev".to_string(),
+            exit_code: None,
+            signal: Some(11),
+            stderr: Vec::new(),
+        };
+        assert_eq!(crash.to_string(), "sf-dev (black) was killed by signal 11");
+
+        let exited = CrashReport { side: Side::White, exit_code: Some(134), signal: None, ..crash };
+        assert_eq!(exited.to_string(), "sf-dev (white) exited with code 134");
+    }
+
+    #[test]
+    fn names_variants_like_uci_variant() {
+        let variant: Variant = "3check".parse().expect("parse variant");
+        assert_eq!(variant, Variant::ThreeCheck);
+        assert_eq!(variant.rules().uci(), "3check");
+        assert_eq!(serde_json::to_string(&variant).expect("serialize"), "\"3check\"");
+        assert_eq!(serde_json::to_string(&Variant::KingOfTheHill).expect("serialize"), "\"kingofthehill\"");
+        assert_eq!("racingkings".parse::<Variant>(), Ok(Variant::RacingKings));
+        assert!("shogi".parse::<Variant>().is_err());
+    }
 }
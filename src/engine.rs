@@ -1,4 +1,16 @@
 use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, timeout};
+
+use crate::config::Protocol;
+use crate::domain::Variant;
+use crate::transcript::{Direction, Transcript};
+use crate::uci::UciError;
 
 #[derive(Debug, Clone)]
 pub struct EngineSpec {
@@ -8,4 +20,209 @@ pub struct EngineSpec {
     pub path: PathBuf,
     pub args: Vec<String>,
     pub working_dir: Option<PathBuf>,
+    pub protocol: Protocol,
+    /// Engine options from the config, as name/value pairs.
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineSpec {
+    /// The configured options with `name` set to `value`, replacing any configured value.
+    /// Option names compare case-insensitively, as in UCI.
+    pub fn options_with(&self, name: &str, value: &str) -> Vec<(String, String)> {
+        let mut options: Vec<_> =
+            self.options.iter().filter(|(option, _)| !option.eq_ignore_ascii_case(name)).cloned().collect();
+        options.push((name.to_string(), value.to_string()));
+        options
+    }
+
+    /// The configured options plus what the engine needs to play `variant`.
+    pub fn options_for(&self, variant: Variant) -> Vec<(String, String)> {
+        match variant {
+            Variant::Standard => self.options.clone(),
+            Variant::Chess960 => self.options_with("UCI_Chess960", "true"),
+            _ => self.options_with("UCI_Variant", variant.rules().uci()),
+        }
+    }
+}
+
+/// An engine child process, spoken to one line at a time over stdin and stdout. Protocol
+/// drivers such as `UciProcess` and `XboardProcess` are built on top of it. Every line in
+/// either direction, and everything the engine writes to stderr, goes into its transcript.
+pub struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// Bytes of a line not yet completed, kept when a read is cancelled by a timeout.
+    partial: Vec<u8>,
+    stderr: Option<JoinHandle<()>>,
+    transcript: Transcript,
+    stall_timeout: Option<Duration>,
+}
+
+impl EngineProcess {
+    pub async fn spawn(
+        path: &PathBuf,
+        args: &[String],
+        working_dir: Option<&PathBuf>,
+        transcript: Transcript,
+    ) -> Result<Self, UciError> {
+        let mut command = Command::new(path);
+        command.args(args);
+        command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        // An engine dropped on an error path, without `quit`, must not outlive its game.
+        command.kill_on_drop(true);
+        if let Some(dir) = working_dir {
+            command.current_dir(dir);
+        }
+
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().ok_or(UciError::UnexpectedEof)?;
+        let stdout = child.stdout.take().ok_or(UciError::UnexpectedEof)?;
+        let stdout = BufReader::new(stdout);
+
+        let stderr = child.stderr.take().map(|stderr| {
+            let transcript = transcript.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    transcript.record(Direction::Stderr, line.trim_end());
+                }
+            })
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            partial: Vec::new(),
+            stderr,
+            transcript,
+            stall_timeout: None,
+        })
+    }
+
+    pub async fn send_line(&mut self, line: &str) -> Result<(), UciError> {
+        self.transcript.record(Direction::Sent, line);
+        self.stdin.write_all(line.as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    /// Reads one line. Safe to cancel: a partly read line is finished by the next call.
+    pub async fn read_line(&mut self) -> Result<String, UciError> {
+        let bytes = self.stdout.read_until(b'\n', &mut self.partial).await?;
+        if bytes == 0 && !self.partial.ends_with(b"\n") {
+            return Err(UciError::UnexpectedEof);
+        }
+        let line = String::from_utf8_lossy(&self.partial).trim().to_string();
+        self.partial.clear();
+        self.transcript.record(Direction::Received, &line);
+        Ok(line)
+    }
+
+    /// How long the engine may stay silent during a search before it is pinged, and again
+    /// after the ping before it counts as stalled. `None` disables the watchdog.
+    pub fn set_stall_timeout(&mut self, stall_timeout: Option<Duration>) {
+        self.stall_timeout = stall_timeout;
+    }
+
+    /// Reads the next line of a running search, or `None` once `deadline` has passed. With
+    /// a stall timeout, `ping` is sent after that long without output, and the engine is
+    /// stalled if it then stays silent as long again. Without a ping command silence
+    /// proves nothing, so such engines only have the deadline of their clock.
+    pub async fn read_search_line(&mut self, deadline: Instant, ping: Option<&str>) -> Result<Option<String>, UciError> {
+        let stall_timeout = self.stall_timeout.filter(|_| ping.is_some());
+        let mut pinged = false;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let wait = match stall_timeout {
+                Some(stall) => stall.min(deadline - now),
+                None => deadline - now,
+            };
+            if let Ok(line) = timeout(wait, self.read_line()).await {
+                return line.map(Some);
+            }
+            if stall_timeout.is_none() || Instant::now() >= deadline {
+                continue;
+            }
+            match ping {
+                Some(ping) if !pinged => {
+                    self.send_line(ping).await?;
+                    pinged = true;
+                }
+                _ => return Err(UciError::Stalled),
+            }
+        }
+    }
+
+    /// How long to wait for the process to exit once its pipes have closed.
+    const EXIT_WAIT: Duration = Duration::from_secs(1);
+
+    /// The exit status if the process has exited, waiting briefly since a closed pipe is
+    /// usually seen a moment before the exit. `None` means it is still running. Once it has
+    /// exited, the rest of its stderr is drained into the transcript.
+    pub async fn exit_status(&mut self) -> Option<ExitStatus> {
+        let status = timeout(Self::EXIT_WAIT, self.child.wait()).await.ok()?.ok()?;
+        if let Some(stderr) = self.stderr.take() {
+            let _ = timeout(Self::EXIT_WAIT, stderr).await;
+        }
+        Some(status)
+    }
+
+    /// Sends the protocol's quit command and waits for the process to exit, killing it if
+    /// it does not.
+    pub async fn quit(mut self, command: &str) {
+        let _ = self.send_line(command).await;
+        if timeout(Self::EXIT_WAIT, self.child.wait()).await.is_err() {
+            let _ = self.child.kill().await;
+        }
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        self.transcript.close();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    async fn shell(script: &str) -> EngineProcess {
+        let args = vec!["-c".to_string(), script.to_string()];
+        EngineProcess::spawn(&PathBuf::from("sh"), &args, None, Transcript::default()).await.expect("spawn sh")
+    }
+
+    #[tokio::test]
+    async fn silent_engines_are_pinged_then_stalled() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        let mut silent = shell("while read line; do [ \"$line\" = quit ] && exit; done").await;
+        silent.set_stall_timeout(Some(Duration::from_millis(50)));
+        assert!(matches!(silent.read_search_line(deadline, Some("isready")).await, Err(UciError::Stalled)));
+        silent.quit("quit").await;
+
+        let mut answering = shell("while read line; do [ \"$line\" = quit ] && exit; echo readyok; done").await;
+        answering.set_stall_timeout(Some(Duration::from_millis(50)));
+        let line = answering.read_search_line(deadline, Some("isready")).await.expect("read");
+        assert_eq!(line.as_deref(), Some("readyok"));
+        answering.quit("quit").await;
+
+        let mut unwatched = shell("read line").await;
+        let soon = Instant::now() + Duration::from_millis(100);
+        assert!(matches!(unwatched.read_search_line(soon, None).await, Ok(None)));
+        unwatched.quit("quit").await;
+
+        // Without a ping command, silence longer than the stall timeout is not a stall.
+        let mut no_ping = shell("read line").await;
+        no_ping.set_stall_timeout(Some(Duration::from_millis(20)));
+        let soon = Instant::now() + Duration::from_millis(100);
+        assert!(matches!(no_ping.read_search_line(soon, None).await, Ok(None)));
+        no_ping.quit("quit").await;
+    }
 }
@@ -0,0 +1,293 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use shakmaty::EnPassantMode;
+use shakmaty::fen::Fen;
+
+use crate::adjudication::AdjudicationRules;
+use crate::chess960;
+use crate::domain::{Side, TimeControl, Variant};
+use crate::engine::EngineSpec;
+use crate::match_runner::{MatchConfig, play_game, start_position_for};
+use crate::pgn::{GameInfo, game_record};
+use crate::review::{Classification, GameReview, review_game};
+use crate::stats::Score;
+use crate::syzygy::Tablebases;
+use crate::transcript::MatchTranscripts;
+
+pub struct HeadlessMatchOptions {
+    pub white: String,
+    pub black: String,
+    pub time_control: TimeControl,
+    pub games: u32,
+    pub openings: Option<PathBuf>,
+    pub pgn_out: Option<PathBuf>,
+    /// Engine that reviews every finished game; its counts are printed and its annotations go
+    /// into the PGN.
+    pub review_engine: Option<String>,
+    pub review_depth: u32,
+    /// Directory for per-game engine transcripts, `game-<n>-<white|black>-<id>.log`.
+    pub log_dir: Option<PathBuf>,
+    /// Stall watchdog in milliseconds; zero disables it.
+    pub stall_timeout_ms: u64,
+    pub timemargin_ms: u64,
+    pub adjudication: AdjudicationRules,
+    /// Directory of Syzygy tables to adjudicate endgames with.
+    pub syzygy_path: Option<PathBuf>,
+    /// Largest number of pieces adjudicated by the tables; all they cover by default.
+    pub syzygy_pieces: Option<usize>,
+    pub variant: Variant,
+    /// Chess960 position for every game; a random one per game pair when `None`.
+    pub chess960_index: Option<u32>,
+}
+
+/// Plays `games` games between two engines without the HTTP server, alternating colours
+/// each game. With an openings file every opening is played twice, once with each side.
+/// Returns the score from the point of view of `options.white`.
+pub async fn run_headless_match(engines: &[EngineSpec], options: &HeadlessMatchOptions) -> Result<Score, String> {
+    let first = find_engine(engines, &options.white)?;
+    let second = find_engine(engines, &options.black)?;
+    let reviewer = match &options.review_engine {
+        Some(id) => Some(find_engine(engines, id)?),
+        None => None,
+    };
+
+    let openings = match &options.openings {
+        Some(path) => load_openings(path, options.variant)?,
+        None => Vec::new(),
+    };
+    let chess960_seed = chess960::random_seed();
+
+    let mut pgn_file = match &options.pgn_out {
+        Some(path) => Some(File::create(path).map_err(|err| format!("failed to create {}: {err}", path.display()))?),
+        None => None,
+    };
+
+    let tablebases = match &options.syzygy_path {
+        Some(dir) => Some(Arc::new(Tablebases::open(dir, options.syzygy_pieces)?)),
+        None => None,
+    };
+    if let Some(tables) = &tablebases {
+        println!("Adjudicating positions with up to {} pieces by tablebase", tables.max_pieces());
+    }
+
+    if let Some(dir) = &options.log_dir {
+        fs::create_dir_all(dir).map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+    }
+
+    let mut score = Score::default();
+    for game in 0..options.games {
+        let first_is_white = game % 2 == 0;
+        let (white, black) = if first_is_white { (first, second) } else { (second, first) };
+        let start_fen =
+            pair_start_fen(&openings, options.variant, options.chess960_index, chess960_seed, (game / 2) as usize)?;
+
+        println!("Started game {} of {} ({} vs {})", game + 1, options.games, white.id, black.id);
+
+        let config = MatchConfig {
+            white: white.clone(),
+            black: black.clone(),
+            time_control: options.time_control,
+            variant: options.variant,
+            start_fen: start_fen.clone(),
+            transcripts: MatchTranscripts::default(),
+            stall_timeout_ms: options.stall_timeout_ms,
+            timemargin_ms: options.timemargin_ms,
+            adjudication: options.adjudication,
+            tablebases: tablebases.clone(),
+            human: None,
+        };
+        let transcripts = config.transcripts.clone();
+        let state = play_game(format!("game-{}", game + 1), config).await;
+
+        if let Some(dir) = &options.log_dir {
+            for (side, engine, transcript) in
+                [("white", white, &transcripts.white), ("black", black, &transcripts.black)]
+            {
+                let path = dir.join(format!("game-{}-{side}-{}.log", game + 1, engine.id));
+                fs::write(&path, transcript.to_text())
+                    .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+            }
+        }
+
+        let (result, reason) = match &state.result {
+            Some(result) => (result.result.clone(), result.reason.to_string()),
+            None => ("*".to_string(), "unfinished".to_string()),
+        };
+        score.record(&result, first_is_white);
+
+        println!("Finished game {} ({} vs {}): {result} {{{reason}}}", game + 1, white.id, black.id);
+        if let Some(crash) = &state.crash {
+            println!("  {crash}");
+            for line in &crash.stderr {
+                println!("  stderr: {line}");
+            }
+        }
+        println!(
+            "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+            first.id,
+            second.id,
+            score.wins,
+            score.losses,
+            score.draws,
+            score.ratio().unwrap_or(0.0),
+            score.games()
+        );
+
+        // A failed review only costs the annotations; the game itself was played.
+        let mut review = None;
+        if let Some(reviewer) = reviewer {
+            println!("Reviewing game {} with {} at depth {}", game + 1, reviewer.id, options.review_depth);
+            match review_game(reviewer, &state.start_fen, state.variant, &state.moves, options.review_depth).await {
+                Ok(done) => {
+                    print_review(&done, Side::to_move(&state.start_fen), &white.id, &black.id);
+                    review = Some(done);
+                }
+                Err(err) => eprintln!("warning: review of game {} failed: {err}", game + 1),
+            }
+        }
+
+        if let Some(file) = pgn_file.as_mut() {
+            let info = GameInfo {
+                event: "chessbench match",
+                round: game + 1,
+                white: &white.id,
+                black: &black.id,
+                time_control: options.time_control,
+            };
+            let movetext = review.map_or_else(|| state.pgn.clone(), |review| review.pgn);
+            let record = game_record(&info, &state, &movetext, start_fen.is_none());
+            file.write_all(record.as_bytes()).map_err(|err| format!("failed to write pgn: {err}"))?;
+        }
+    }
+
+    print_summary(&first.id, &second.id, &score);
+    Ok(score)
+}
+
+/// Prints how many inaccuracies, mistakes and blunders each side made.
+fn print_review(review: &GameReview, first_mover: Side, white: &str, black: &str) {
+    for (side, id) in [(Side::White, white), (Side::Black, black)] {
+        let mut counts = [0; 3];
+        // The side that moved first plays the odd plies.
+        let moved_by_side = |ply: u32| (ply % 2 == 1) == (first_mover == side);
+        for reviewed in review.moves.iter().filter(|reviewed| moved_by_side(reviewed.ply)) {
+            let index = match reviewed.classification {
+                Some(Classification::Inaccuracy) => 0,
+                Some(Classification::Mistake) => 1,
+                Some(Classification::Blunder) => 2,
+                None => continue,
+            };
+            counts[index] += 1;
+        }
+        println!("  {id}: {} inaccuracies, {} mistakes, {} blunders", counts[0], counts[1], counts[2]);
+    }
+}
+
+pub fn print_summary(first: &str, second: &str, score: &Score) {
+    println!(
+        "Final score of {first} vs {second}: {} - {} - {}  [{:.3}] {}",
+        score.wins,
+        score.losses,
+        score.draws,
+        score.ratio().unwrap_or(0.0),
+        score.games()
+    );
+    match score.elo() {
+        Some((elo, margin)) => println!("Elo difference: {elo:.1} +/- {margin:.1}"),
+        None => println!("Elo difference: n/a"),
+    }
+}
+
+fn find_engine<'a>(engines: &'a [EngineSpec], id: &str) -> Result<&'a EngineSpec, String> {
+    engines
+        .iter()
+        .find(|engine| engine.id == id)
+        .ok_or_else(|| format!("unknown or unavailable engine id: {id}"))
+}
+
+/// The start position for game pair `pair`: the next opening if there are any, otherwise
+/// a Chess960 position (`chess960_index`, or drawn from `seed`), otherwise `None` for the
+/// variant's usual one.
+pub fn pair_start_fen(
+    openings: &[String],
+    variant: Variant,
+    chess960_index: Option<u32>,
+    seed: u64,
+    pair: usize,
+) -> Result<Option<String>, String> {
+    if !openings.is_empty() {
+        return Ok(Some(openings[pair % openings.len()].clone()));
+    }
+    match variant {
+        Variant::Chess960 => {
+            let index = chess960_index.unwrap_or_else(|| chess960::pair_index(seed, pair));
+            chess960::start_fen(index).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Reads starting positions, one FEN or EPD per line. EPD lines keep only their four
+/// position fields. Blank lines and `#` comments are skipped.
+pub fn load_openings(path: &Path, variant: Variant) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+
+    let mut openings = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fen = opening_fen(line, variant).map_err(|err| format!("{}:{}: {err}", path.display(), idx + 1))?;
+        openings.push(fen);
+    }
+
+    if openings.is_empty() {
+        return Err(format!("no positions found in {}", path.display()));
+    }
+
+    Ok(openings)
+}
+
+/// The FEN of an opening line: a full FEN as `variant` writes it (three-check adds a
+/// counter field), or the four position fields of an EPD line, without its opcodes.
+fn opening_fen(line: &str, variant: Variant) -> Result<String, String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let mut error = None;
+    for len in (4..=fields.len().min(7)).rev() {
+        match start_position_for(&fields[..len].join(" "), variant) {
+            Ok(pos) => return Ok(Fen::from_position(pos, EnPassantMode::Legal).to_string()),
+            Err(err) => error = Some(err),
+        }
+    }
+    Err(error.unwrap_or_else(|| format!("invalid fen {line}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_full_fens_and_trims_epd_opcodes() {
+        let e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        assert_eq!(opening_fen(e4, Variant::Standard).as_deref(), Ok(e4));
+        let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"e4\";";
+        assert_eq!(opening_fen(epd, Variant::Standard).as_deref(), Ok(e4));
+        assert!(opening_fen("rnbqkbnr/pppppppp/8/8 b KQkq -", Variant::Standard).is_err());
+    }
+
+    #[test]
+    fn keeps_three_check_counters() {
+        let checked = "rnbqkbnr/ppp2ppp/8/1B1pp3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 2+3 1 3";
+        assert_eq!(opening_fen(checked, Variant::ThreeCheck).as_deref(), Ok(checked));
+        let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"e4\";";
+        assert_eq!(
+            opening_fen(epd, Variant::ThreeCheck).as_deref(),
+            Ok("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 3+3 0 1")
+        );
+    }
+}
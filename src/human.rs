@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use crate::config::Protocol;
+use crate::domain::TimeControl;
+use crate::engine::EngineSpec;
+use crate::protocol::EngineProtocol;
+use crate::uci::{SearchInfo, SearchLimits, UciEngineInfo, UciError};
+
+/// Engine id that seats a human player instead of an engine.
+pub const HUMAN_ID: &str = "human";
+
+/// A move sent by the human player, with the ply it was checked against.
+#[derive(Debug, Clone)]
+pub struct HumanMove {
+    pub ply: u32,
+    pub uci: String,
+}
+
+/// The spec standing in for the human side of a match; it is never spawned.
+pub fn human_spec() -> EngineSpec {
+    EngineSpec {
+        id: HUMAN_ID.to_string(),
+        name: "Human".to_string(),
+        author: String::new(),
+        path: PathBuf::new(),
+        args: Vec::new(),
+        working_dir: None,
+        protocol: Protocol::Uci,
+        options: Vec::new(),
+    }
+}
+
+/// A player whose moves arrive over the match's WebSocket. Searching waits for the next
+/// move on the clock; moves sent for an earlier ply are dropped.
+pub struct HumanPlayer {
+    moves: mpsc::Receiver<HumanMove>,
+}
+
+impl HumanPlayer {
+    pub fn new(moves: mpsc::Receiver<HumanMove>) -> Self {
+        Self { moves }
+    }
+
+    async fn next_move(&mut self, ply: u32, timeout_ms: u64) -> Result<String, UciError> {
+        let wait = async {
+            while let Some(mv) = self.moves.recv().await {
+                if mv.ply == ply {
+                    return Ok(mv.uci);
+                }
+            }
+            Err(UciError::UnexpectedEof)
+        };
+        timeout(Duration::from_millis(timeout_ms), wait).await.map_err(|_| UciError::Timeout("human move"))?
+    }
+}
+
+impl EngineProtocol for HumanPlayer {
+    fn handshake(&mut self) -> BoxFuture<'_, Result<UciEngineInfo, UciError>> {
+        Box::pin(async { Ok(UciEngineInfo { name: "Human".to_string(), author: String::new(), options: Vec::new() }) })
+    }
+
+    fn configure<'a>(&'a mut self, _options: &'a [(String, String)]) -> BoxFuture<'a, Result<(), UciError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn is_ready(&mut self) -> BoxFuture<'_, Result<(), UciError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn new_game(&mut self, _time_control: Option<TimeControl>) -> BoxFuture<'_, Result<(), UciError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn search<'a>(
+        &'a mut self,
+        _start_fen: &'a str,
+        moves: &'a [String],
+        _limits: &'a SearchLimits,
+        timeout_ms: u64,
+        _on_info: &'a mut (dyn FnMut(SearchInfo) + Send),
+    ) -> BoxFuture<'a, Result<String, UciError>> {
+        Box::pin(self.next_move(moves.len() as u32, timeout_ms))
+    }
+
+    /// Humans get their whole clock to think.
+    fn set_stall_timeout(&mut self, _stall_timeout: Option<Duration>) {}
+
+    fn exit_status(&mut self) -> BoxFuture<'_, Option<ExitStatus>> {
+        Box::pin(async { None })
+    }
+
+    fn quit(self: Box<Self>) -> BoxFuture<'static, ()> {
+        Box::pin(async {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn waits_for_the_move_of_the_current_ply() {
+        let (sender, receiver) = mpsc::channel(4);
+        let mut player = HumanPlayer::new(receiver);
+        sender.send(HumanMove { ply: 0, uci: "e2e4".to_string() }).await.expect("send");
+        sender.send(HumanMove { ply: 1, uci: "e7e5".to_string() }).await.expect("send");
+
+        // The stale move for ply 0 is skipped.
+        assert_eq!(player.next_move(1, 1_000).await.expect("move"), "e7e5");
+        assert!(matches!(player.next_move(2, 10).await, Err(UciError::Timeout(_))));
+    }
+}
@@ -1,74 +1,473 @@
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
 use std::{fs, process};
 
+mod adjudication;
+mod analysis;
 mod api;
+mod bench;
+mod chess960;
 mod config;
+mod diagnostics;
 mod domain;
 mod engine;
+mod headless;
+mod human;
 mod match_runner;
+mod pgn;
+mod protocol;
+mod review;
 mod server;
+mod stats;
+mod suite;
+mod syzygy;
+mod tournament;
+mod transcript;
 mod uci;
+mod xboard;
+
+use adjudication::{AdjudicationRules, DrawRule, ResignRule};
+use domain::{TimeControl, Variant};
+use engine::EngineSpec;
+use syzygy::Tablebases;
 
 #[derive(Debug, Parser)]
-#[command(name = "chessbench", version, about = "UCI engine vs engine server")]
+#[command(
+    name = "chessbench",
+    version,
+    about = "UCI engine vs engine server",
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
 struct Cli {
     #[arg(long, default_value = "0.0.0.0:8080")]
     bind: String,
+    #[arg(long, value_name = "PATH", required = true)]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    syzygy: SyzygyArgs,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Syzygy tablebase adjudication, shared by the server and `match`.
+#[derive(Debug, Args)]
+struct SyzygyArgs {
+    /// Directory of Syzygy tables; games reaching a position they cover end with its result
+    #[arg(long, value_name = "DIR")]
+    syzygy_path: Option<PathBuf>,
+    /// Only adjudicate positions with at most this many pieces (default: all the tables cover)
+    #[arg(long, value_name = "N", requires = "syzygy_path")]
+    syzygy_pieces: Option<usize>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Play a match between two engines without starting the server
+    Match(MatchArgs),
+    /// Run or resume a round-robin, gauntlet or SPRT tournament
+    Tournament(TournamentArgs),
+    /// Check an engine config file and report every problem found
+    CheckConfig(CheckConfigArgs),
+    /// Start one engine and print its id, options and handshake timings
+    Probe(ProbeArgs),
+    /// Measure nodes, NPS, time-to-depth and bestmove stability
+    Bench(BenchArgs),
+    /// Run an EPD test suite (bm/am, STS partial credit) against one engine
+    Suite(SuiteArgs),
+}
+
+#[derive(Debug, Args)]
+struct MatchArgs {
+    /// Engine config file
+    #[arg(long, value_name = "PATH", default_value = "engines.toml")]
+    config: PathBuf,
+    /// Engine id that plays white in the first game
+    #[arg(long)]
+    white: String,
+    /// Engine id that plays black in the first game
+    #[arg(long)]
+    black: String,
+    /// Time control as base+increment in seconds
+    #[arg(long, value_name = "BASE+INC", default_value = "10+0.1")]
+    tc: TimeControl,
+    /// Number of games; colours alternate every game
+    #[arg(long, default_value_t = 2)]
+    games: u32,
+    /// File with one FEN or EPD starting position per line
+    #[arg(long, value_name = "PATH")]
+    openings: Option<PathBuf>,
+    /// Write all games to this PGN file
+    #[arg(long, value_name = "PATH")]
+    pgnout: Option<PathBuf>,
+    /// Engine id that reviews every game, printing each side's errors and annotating the PGN with evals and NAGs
+    #[arg(long, value_name = "ID")]
+    review: Option<String>,
+    /// Search depth for the review engine
+    #[arg(long, default_value_t = review::DEFAULT_REVIEW_DEPTH)]
+    review_depth: u32,
+    /// Save a timestamped transcript of each engine's traffic and stderr per game here
+    #[arg(long, value_name = "DIR")]
+    log_dir: Option<PathBuf>,
+    /// Seconds an engine may stay silent mid-search before it is pinged and, if still
+    /// silent as long again, loses as stalled; 0 disables the check
+    #[arg(long, value_name = "SECONDS", default_value_t = match_runner::DEFAULT_STALL_TIMEOUT_MS / 1000)]
+    stall_timeout: u64,
+    /// Milliseconds an engine may go past its clock before it forfeits on time
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    timemargin: u64,
+    /// Adjudicate a loss once both engines agree one side is at least CP behind for N moves
+    #[arg(long, value_name = "movecount=N,score=CP")]
+    resign: Option<ResignRule>,
+    /// Adjudicate a draw once both engines score within ±CP for N moves from move M
+    #[arg(long, value_name = "movenumber=M,movecount=N,score=CP")]
+    draw: Option<DrawRule>,
+    /// Adjudicate a draw once a game reaches this many plies
+    #[arg(long, value_name = "PLIES", value_parser = clap::value_parser!(u32).range(1..))]
+    max_plies: Option<u32>,
+    #[command(flatten)]
+    syzygy: SyzygyArgs,
+    /// Rules to play by: standard, chess960, atomic, crazyhouse, 3check, kingofthehill,
+    /// antichess, horde or racingkings
+    #[arg(long, default_value = "standard")]
+    variant: Variant,
+    /// Chess960 starting position (0-959, 518 is the standard one); random per game pair by default
+    #[arg(long, value_name = "INDEX", value_parser = clap::value_parser!(u32).range(0..960))]
+    chess960_index: Option<u32>,
+}
+
+#[derive(Debug, Args)]
+struct TournamentArgs {
+    /// Tournament description file
     #[arg(long, value_name = "PATH")]
     config: PathBuf,
 }
 
+#[derive(Debug, Args)]
+struct CheckConfigArgs {
+    /// Engine config file
+    #[arg(long, value_name = "PATH", default_value = "engines.toml")]
+    config: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct ProbeArgs {
+    /// Engine id from the config file
+    engine_id: String,
+    /// Engine config file
+    #[arg(long, value_name = "PATH", default_value = "engines.toml")]
+    config: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct BenchArgs {
+    /// Engine config file
+    #[arg(long, value_name = "PATH", default_value = "engines.toml")]
+    config: PathBuf,
+    /// Engine id to benchmark; repeat for several. Defaults to every configured engine
+    #[arg(long = "engine", value_name = "ID")]
+    engines: Vec<String>,
+    /// Search every position to this depth (default 12)
+    #[arg(long, conflicts_with = "movetime")]
+    depth: Option<u32>,
+    /// Search every position for this many milliseconds
+    #[arg(long, value_name = "MS")]
+    movetime: Option<u64>,
+    /// How many times to search each position
+    #[arg(long, default_value_t = 3)]
+    runs: u32,
+    /// File with one FEN or EPD position per line instead of the built-in set
+    #[arg(long, value_name = "PATH")]
+    positions: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct SuiteArgs {
+    /// Engine config file
+    #[arg(long, value_name = "PATH", default_value = "engines.toml")]
+    config: PathBuf,
+    /// Engine id to test
+    #[arg(long)]
+    engine: String,
+    /// EPD file with bm/am or c0 point-list opcodes
+    #[arg(long, value_name = "PATH")]
+    epd: PathBuf,
+    /// Search every position to this depth
+    #[arg(long, conflicts_with = "movetime")]
+    depth: Option<u32>,
+    /// Search every position for this many milliseconds (default 1000)
+    #[arg(long, value_name = "MS")]
+    movetime: Option<u64>,
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
-    let config_text = match fs::read_to_string(&cli.config) {
-        Ok(contents) => contents,
+    match cli.command {
+        Some(Command::Match(args)) => run_match_command(args).await,
+        Some(Command::Tournament(args)) => run_tournament_command(args).await,
+        Some(Command::CheckConfig(args)) => run_check_config_command(args).await,
+        Some(Command::Probe(args)) => run_probe_command(args).await,
+        Some(Command::Bench(args)) => run_bench_command(args).await,
+        Some(Command::Suite(args)) => run_suite_command(args).await,
+        None => {
+            let config = cli.config.expect("clap requires --config without a subcommand");
+            serve(&cli.bind, &config, &cli.syzygy).await;
+        }
+    }
+}
+
+async fn serve(bind: &str, config_path: &Path, syzygy: &SyzygyArgs) {
+    let config = load_config(config_path);
+    let engines = discover(&config.engine).await;
+
+    let tablebases = match &syzygy.syzygy_path {
+        Some(dir) => match Tablebases::open(dir, syzygy.syzygy_pieces) {
+            Ok(tables) => Some(tables),
+            Err(err) => {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let app = server::build_router(engines, tablebases);
+
+    let listener = match tokio::net::TcpListener::bind(bind).await {
+        Ok(listener) => listener,
         Err(err) => {
-            eprintln!("failed to read config {}: {err}", cli.config.display());
+            eprintln!("failed to bind {bind}: {err}");
             process::exit(1);
         }
     };
 
-    let config = match config::EngineConfigFile::from_str(&config_text) {
-        Ok(config) => config,
+    if let Err(err) = axum::serve(listener, app).await {
+        eprintln!("server error: {err}");
+        process::exit(1);
+    }
+}
+
+async fn run_match_command(args: MatchArgs) {
+    let config = load_config(&args.config);
+
+    // Only the engines taking part (and the reviewer) need to go through discovery.
+    let selected: Vec<_> = config
+        .engine
+        .iter()
+        .filter(|entry| entry.id == args.white || entry.id == args.black || args.review.as_ref() == Some(&entry.id))
+        .cloned()
+        .collect();
+    let engines = discover(&selected).await;
+
+    let options = headless::HeadlessMatchOptions {
+        white: args.white,
+        black: args.black,
+        time_control: args.tc,
+        games: args.games,
+        openings: args.openings,
+        pgn_out: args.pgnout,
+        review_engine: args.review,
+        review_depth: args.review_depth,
+        log_dir: args.log_dir,
+        stall_timeout_ms: args.stall_timeout * 1000,
+        timemargin_ms: args.timemargin,
+        adjudication: AdjudicationRules {
+            resign: args.resign,
+            draw: args.draw,
+            max_plies: args.max_plies,
+        },
+        syzygy_path: args.syzygy.syzygy_path,
+        syzygy_pieces: args.syzygy.syzygy_pieces,
+        variant: args.variant,
+        chess960_index: args.chess960_index,
+    };
+
+    if let Err(err) = headless::run_headless_match(&engines, &options).await {
+        eprintln!("match failed: {err}");
+        process::exit(1);
+    }
+}
+
+async fn run_tournament_command(args: TournamentArgs) {
+    let tournament = match tournament::TournamentConfig::load(&args.config) {
+        Ok(tournament) => tournament,
         Err(err) => {
-            eprintln!("invalid config format: {err}");
+            eprintln!("{err}");
             process::exit(1);
         }
     };
 
-    if let Err(err) = config.validate() {
-        eprintln!("invalid config contents: {err:?}");
+    let config = load_config(&tournament.engines);
+    let selected: Vec<_> = config
+        .engine
+        .iter()
+        .filter(|entry| tournament.players.contains(&entry.id))
+        .cloned()
+        .collect();
+    let engines = discover(&selected).await;
+
+    if let Err(err) = tournament::run_tournament(&tournament, &engines, |_, _, _| {}).await {
+        eprintln!("tournament failed: {err}");
         process::exit(1);
     }
+}
 
-    let engines = match uci::discover_engines(&config.engine).await {
-        Ok(engines) => engines,
+async fn run_check_config_command(args: CheckConfigArgs) {
+    let config = read_config(&args.config);
+    let problems = diagnostics::check_config(&config).await;
+
+    if problems.is_empty() {
+        println!("{}: {} engine(s), no problems found", args.config.display(), config.engine.len());
+        return;
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    eprintln!("{}: {} problem(s) found", args.config.display(), problems.len());
+    process::exit(1);
+}
+
+async fn run_probe_command(args: ProbeArgs) {
+    let config = load_config(&args.config);
+    let Some(entry) = config.engine.iter().find(|entry| entry.id == args.engine_id) else {
+        eprintln!("unknown engine id: {}", args.engine_id);
+        process::exit(1);
+    };
+
+    match diagnostics::probe_engine(entry).await {
+        Ok(report) => diagnostics::print_probe(&entry.id, &report),
         Err(err) => {
-            eprintln!("engine discovery failed: {err}");
+            eprintln!("probe of {} failed: {err}", entry.id);
             process::exit(1);
         }
+    }
+}
+
+async fn run_bench_command(args: BenchArgs) {
+    let config = load_config(&args.config);
+    if let Some(id) = args.engines.iter().find(|id| !config.engine.iter().any(|entry| &entry.id == *id)) {
+        eprintln!("unknown engine id: {id}");
+        process::exit(1);
+    }
+
+    let selected: Vec<_> = config
+        .engine
+        .iter()
+        .filter(|entry| args.engines.is_empty() || args.engines.contains(&entry.id))
+        .cloned()
+        .collect();
+    let engines = discover(&selected).await;
+
+    let limit = match (args.depth, args.movetime) {
+        (_, Some(ms)) => bench::BenchLimit::Movetime(ms),
+        (depth, None) => bench::BenchLimit::Depth(depth.unwrap_or(12)),
+    };
+    let options = bench::BenchOptions {
+        limit,
+        runs: args.runs,
+        positions: args.positions,
     };
 
-    if engines.is_empty() {
-        eprintln!("no engines available after discovery");
+    if let Err(err) = bench::run_bench(&engines, &options).await {
+        eprintln!("bench failed: {err}");
         process::exit(1);
     }
+}
 
-    let app = server::build_router(engines);
+async fn run_suite_command(args: SuiteArgs) {
+    let text = match fs::read_to_string(&args.epd) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("failed to read {}: {err}", args.epd.display());
+            process::exit(1);
+        }
+    };
+    let positions = match suite::parse_epd(&text) {
+        Ok(positions) => positions,
+        Err(err) => {
+            eprintln!("{}: {err}", args.epd.display());
+            process::exit(1);
+        }
+    };
 
-    let listener = match tokio::net::TcpListener::bind(&cli.bind).await {
-        Ok(listener) => listener,
+    let config = load_config(&args.config);
+    let selected: Vec<_> = config.engine.iter().filter(|entry| entry.id == args.engine).cloned().collect();
+    let engines = discover(&selected).await;
+
+    let limit = match (args.depth, args.movetime) {
+        (Some(depth), _) => bench::BenchLimit::Depth(depth),
+        (None, movetime) => bench::BenchLimit::Movetime(movetime.unwrap_or(suite::DEFAULT_MOVETIME_MS)),
+    };
+    println!("Running {} positions with {} ({limit})", positions.len(), engines[0].id);
+
+    let mut done = 0;
+    let result = suite::run_suite(&engines[0], &positions, limit, |position| {
+        done += 1;
+        let id = position.id.clone().unwrap_or_else(|| format!("#{done}"));
+        let verdict = if position.solved { "ok" } else { "FAIL" };
+        let played = position.san.as_deref().unwrap_or(&position.bestmove);
+        println!("{verdict:<4} {id:<32} {played:<8} {}/{}", position.points, position.max_points);
+    })
+    .await;
+
+    match result {
+        Ok(report) => println!(
+            "Solved {}/{}, score {}/{}",
+            report.solved, report.total, report.points, report.max_points
+        ),
+        Err(err) => {
+            eprintln!("suite failed: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn load_config(path: &Path) -> config::EngineConfigFile {
+    let config = read_config(path);
+
+    if let Err(err) = config.validate() {
+        eprintln!("invalid config contents: {err}");
+        process::exit(1);
+    }
+
+    config
+}
+
+/// Reads and parses a config file without validating its contents.
+fn read_config(path: &Path) -> config::EngineConfigFile {
+    let config_text = match fs::read_to_string(path) {
+        Ok(contents) => contents,
         Err(err) => {
-            eprintln!("failed to bind {}: {err}", cli.bind);
+            eprintln!("failed to read config {}: {err}", path.display());
             process::exit(1);
         }
     };
 
-    if let Err(err) = axum::serve(listener, app).await {
-        eprintln!("server error: {err}");
+    match config::EngineConfigFile::from_str(&config_text) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("invalid config format: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+async fn discover(configs: &[config::EngineConfig]) -> Vec<EngineSpec> {
+    let engines = match uci::discover_engines(configs).await {
+        Ok(engines) => engines,
+        Err(err) => {
+            eprintln!("engine discovery failed: {err}");
+            process::exit(1);
+        }
+    };
+
+    if engines.is_empty() {
+        eprintln!("no engines available after discovery");
         process::exit(1);
     }
+
+    engines
 }
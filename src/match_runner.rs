@@ -1,99 +1,345 @@
 use std::collections::HashMap;
+use std::process::ExitStatus;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, broadcast, mpsc, watch};
 
 use shakmaty::fen::Fen;
 use shakmaty::san::San;
 use shakmaty::uci::UciMove;
-use shakmaty::{Chess, Color, EnPassantMode, Outcome, Position};
+use shakmaty::variant::VariantPosition;
+use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Outcome, Position};
 
-use crate::domain::{Clock, MatchResult, MatchState, MatchStatus, ResultReason, Side};
+use crate::adjudication::{AdjudicationRules, Adjudicator};
+use crate::api::{ClockEvent, ErrorEvent, InfoEvent, MatchEvent, MatchStartedEvent, MoveEvent, ResultEvent};
+use crate::domain::{Clock, CrashReport, Latency, MatchResult, MatchState, MatchStatus, MoveSnapshot, MoveStats, ResultReason, RunningClock, Side, TimeControl, Variant, unix_time_ms};
 use crate::engine::EngineSpec;
-use crate::uci::{UciError, UciProcess};
+use crate::human::{HumanMove, HumanPlayer};
+use crate::pgn::format_movetext;
+use crate::protocol::{EngineProtocol, spawn_spec};
+use crate::syzygy::Tablebases;
+use crate::transcript::{MatchTranscripts, Transcript};
+use crate::uci::{SearchInfo, SearchLimits, UciError};
+
+/// How many stderr lines of a crashed engine go into the match record.
+const CRASH_STDERR_LINES: usize = 20;
+
+/// Default stall timeout; see `MatchConfig::stall_timeout_ms`.
+pub const DEFAULT_STALL_TIMEOUT_MS: u64 = 10_000;
+
+/// Pings sent to each engine before a game; the fastest round trip is its latency.
+const LATENCY_PINGS: u32 = 3;
+
+/// Capacity of a match's event channel. Subscribers that fall further behind than this
+/// get `Lagged` and are expected to catch up from the shared `MatchState`.
+const EVENT_CAPACITY: usize = 1024;
+
+/// Requested run state for a match, sent from the API to the runner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchControl {
+    Run,
+    Pause,
+    Abort,
+}
+
+/// Everything needed to play one game.
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    pub white: EngineSpec,
+    pub black: EngineSpec,
+    pub time_control: TimeControl,
+    pub variant: Variant,
+    /// Starting position; `None` means the variant's initial position. Chess960 has no
+    /// single initial position, so callers must choose one.
+    pub start_fen: Option<String>,
+    /// Where each engine's traffic and stderr are recorded.
+    pub transcripts: MatchTranscripts,
+    /// An engine silent this long during a search is pinged, and loses as stalled if it
+    /// stays silent as long again. Zero disables the watchdog.
+    pub stall_timeout_ms: u64,
+    /// How far past its clock an engine may go before it forfeits. The overrun is not
+    /// carried over: the clock just stops at zero.
+    pub timemargin_ms: u64,
+    pub adjudication: AdjudicationRules,
+    /// Syzygy tables that end the game once it reaches a position they cover.
+    pub tablebases: Option<Arc<Tablebases>>,
+    /// The side played by a human over the match's `moves` channel; its engine spec is
+    /// not spawned.
+    pub human: Option<Side>,
+}
+
+/// The channels used to talk to a running match: commands go in through `control`,
+/// a human player's moves through `moves`, typed events come out of `events`.
+#[derive(Debug, Clone)]
+pub struct MatchHandle {
+    pub control: watch::Sender<MatchControl>,
+    pub moves: mpsc::Sender<HumanMove>,
+    pub events: broadcast::Sender<MatchEvent>,
+}
+
+/// The runner's ends of a `MatchHandle`.
+#[derive(Debug)]
+pub struct MatchInbox {
+    pub control: watch::Receiver<MatchControl>,
+    pub moves: mpsc::Receiver<HumanMove>,
+}
+
+impl MatchHandle {
+    pub fn new() -> (Self, MatchInbox) {
+        let (control, control_rx) = watch::channel(MatchControl::Run);
+        // One pending move at most: the human only moves on their own turn.
+        let (moves, moves_rx) = mpsc::channel(1);
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        (Self { control, moves, events }, MatchInbox { control: control_rx, moves: moves_rx })
+    }
+}
 
 pub async fn run_match(
     match_id: String,
-    white: EngineSpec,
-    black: EngineSpec,
-    initial_ms: u64,
+    config: MatchConfig,
     matches: Arc<RwLock<HashMap<String, MatchState>>>,
+    inbox: MatchInbox,
+    events: broadcast::Sender<MatchEvent>,
 ) {
     let match_id_clone = match_id.clone();
-    if let Err(err) = run_match_inner(match_id_clone, white, black, initial_ms, matches.clone()).await {
-        let mut guard = matches.write().await;
-        if let Some(entry) = guard.get_mut(&match_id) {
-            entry.status = MatchStatus::Error;
-            entry.result = Some(MatchResult {
-                result: "*".to_string(),
-                reason: ResultReason::Error,
-            });
+    let result = run_match_inner(match_id_clone, config, matches.clone(), inbox, events.clone()).await;
+    if let Err(err) = result {
+        {
+            let mut guard = matches.write().await;
+            if let Some(entry) = guard.get_mut(&match_id) {
+                entry.status = MatchStatus::Error;
+                entry.running = None;
+                entry.result = Some(MatchResult {
+                    result: "*".to_string(),
+                    reason: ResultReason::Error,
+                });
+            }
         }
+        let _ = events.send(MatchEvent::Error(ErrorEvent {
+            message: err.to_string(),
+        }));
+        let _ = events.send(MatchEvent::Result(ResultEvent {
+            result: "*".to_string(),
+            reason: ResultReason::Error,
+        }));
         eprintln!("match runner error: {err}");
     }
 }
 
+/// Plays a single game outside the server and returns its final state. Used by the
+/// headless CLI commands, which have no one to pause or watch the match.
+pub async fn play_game(match_id: String, config: MatchConfig) -> MatchState {
+    let start_fen = match &config.start_fen {
+        Some(fen) => fen.clone(),
+        None => initial_fen(config.variant),
+    };
+    let initial = MatchState::new(match_id.clone(), start_fen, config.variant, config.time_control);
+    let matches = Arc::new(RwLock::new(HashMap::from([(match_id.clone(), initial.clone())])));
+    let (handle, inbox) = MatchHandle::new();
+
+    run_match(match_id.clone(), config, matches.clone(), inbox, handle.events).await;
+
+    let finished = matches.read().await.get(&match_id).cloned();
+    finished.unwrap_or(initial)
+}
+
 async fn run_match_inner(
     match_id: String,
-    white: EngineSpec,
-    black: EngineSpec,
-    initial_ms: u64,
+    config: MatchConfig,
     matches: Arc<RwLock<HashMap<String, MatchState>>>,
+    inbox: MatchInbox,
+    events: broadcast::Sender<MatchEvent>,
 ) -> Result<(), UciError> {
-    let mut white_engine = UciProcess::spawn(&white.path, &white.args, white.working_dir.as_ref()).await?;
-    let mut black_engine = UciProcess::spawn(&black.path, &black.args, black.working_dir.as_ref()).await?;
+    let MatchInbox { mut control, moves: human_moves } = inbox;
+    let MatchConfig {
+        white,
+        black,
+        time_control,
+        variant,
+        start_fen,
+        transcripts,
+        stall_timeout_ms,
+        timemargin_ms,
+        adjudication,
+        tablebases,
+        human,
+    } = config;
+
+    let start_fen = match (start_fen, variant) {
+        (Some(fen), _) => fen,
+        (None, Variant::Chess960) => {
+            return Err(UciError::InvalidResponse("chess960 needs a start position".to_string()));
+        }
+        (None, variant) => initial_fen(variant),
+    };
+    let mut pos = start_position_for(&start_fen, variant).map_err(UciError::InvalidResponse)?;
+    let first_move_number = pos.fullmoves().get();
+    let black_first = pos.turn() == Color::Black;
+    let start_fen = Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string();
+    if let Some(entry) = matches.write().await.get_mut(&match_id) {
+        entry.start_fen = start_fen.clone();
+        entry.current_fen = start_fen.clone();
+        entry.variant = variant;
+    }
+
+    let mut human_moves = Some(human_moves);
+    let mut white_engine: Box<dyn EngineProtocol> = match human {
+        Some(Side::White) => Box::new(HumanPlayer::new(human_moves.take().expect("one human side"))),
+        _ => spawn_spec(&white, transcripts.white.clone()).await?,
+    };
+    let mut black_engine: Box<dyn EngineProtocol> = match human {
+        Some(Side::Black) => Box::new(HumanPlayer::new(human_moves.take().expect("one human side"))),
+        _ => spawn_spec(&black, transcripts.black.clone()).await?,
+    };
+
+    let stall_timeout = (stall_timeout_ms > 0).then(|| Duration::from_millis(stall_timeout_ms));
+    white_engine.set_stall_timeout(stall_timeout);
+    black_engine.set_stall_timeout(stall_timeout);
 
     let _ = white_engine.handshake().await;
     let _ = black_engine.handshake().await;
+    white_engine.configure(&white.options_for(variant)).await?;
+    black_engine.configure(&black.options_for(variant)).await?;
     let _ = white_engine.is_ready().await;
     let _ = black_engine.is_ready().await;
-    let _ = white_engine.ucinewgame().await;
-    let _ = black_engine.ucinewgame().await;
+    let _ = white_engine.new_game(Some(time_control)).await;
+    let _ = black_engine.new_game(Some(time_control)).await;
+
+    let white_latency = measure_latency(white_engine.as_mut()).await;
+    let black_latency = measure_latency(black_engine.as_mut()).await;
+    if let Some(entry) = matches.write().await.get_mut(&match_id) {
+        entry.latency = Latency {
+            white_us: white_latency.as_micros() as u64,
+            black_us: black_latency.as_micros() as u64,
+        };
+    }
 
-    let mut pos = Chess::default();
-    let mut white_ms = initial_ms;
-    let mut black_ms = initial_ms;
+    let _ = events.send(MatchEvent::Started(MatchStartedEvent {
+        match_id: match_id.clone(),
+        variant,
+        start_fen: start_fen.clone(),
+    }));
+
+    let mut white_ms = time_control.initial_ms;
+    let mut black_ms = time_control.initial_ms;
     let mut ply: u32 = 0;
     let mut moves: Vec<String> = Vec::new();
+    let mut uci_moves: Vec<String> = Vec::new();
+    let mut adjudicator = Adjudicator::new(adjudication);
 
     loop {
+        // A pause takes effect between moves: an engine's search is allowed to finish,
+        // and the match only reports itself paused once both clocks are stopped.
+        if *control.borrow() == MatchControl::Pause {
+            set_paused(&matches, &match_id, Some(Clock { white_ms, black_ms })).await;
+            let _ = events.send(MatchEvent::Clock(ClockEvent { white_ms, black_ms, running: None }));
+        }
+        let resumed = wait_while_paused(&mut control).await;
+        set_paused(&matches, &match_id, None).await;
+        if resumed == MatchControl::Abort {
+            set_result(&match_id, "*".to_string(), ResultReason::Aborted, &matches, &events).await;
+            break;
+        }
+
         let side = if pos.turn() == Color::White { Side::White } else { Side::Black };
-        let (engine, remaining_ms) = match side {
-            Side::White => (&mut white_engine, white_ms),
-            Side::Black => (&mut black_engine, black_ms),
+        let (engine, remaining_ms, latency) = match side {
+            Side::White => (&mut white_engine, white_ms, white_latency),
+            Side::Black => (&mut black_engine, black_ms, black_latency),
         };
+        let allowed_ms = remaining_ms + timemargin_ms;
 
-        if remaining_ms == 0 {
-            finish_match(&match_id, side, ResultReason::Timeout, &matches).await;
+        if allowed_ms == 0 {
+            finish_match(&match_id, side, ResultReason::Timeout, &matches, &events).await;
             break;
         }
 
-        let fen = Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string();
-        let position_cmd = format!("position fen {fen}");
-        engine.send_line(&position_cmd).await?;
-
         let start = Instant::now();
-        let bestmove = match engine.bestmove(white_ms, black_ms, remaining_ms).await {
+        if let Some(entry) = matches.write().await.get_mut(&match_id) {
+            entry.running = Some(RunningClock { side, since_ms: unix_time_ms() });
+        }
+        let _ = events.send(MatchEvent::Clock(ClockEvent { white_ms, black_ms, running: Some(side) }));
+        let mut main_line = SearchInfo::default();
+        let mut on_info = |info: SearchInfo| {
+            record_main_line(&mut main_line, &info);
+            let _ = events.send(MatchEvent::Info(InfoEvent::new(side, info)));
+        };
+        let limits = SearchLimits {
+            wtime: Some(white_ms),
+            btime: Some(black_ms),
+            winc: Some(time_control.increment_ms),
+            binc: Some(time_control.increment_ms),
+            ..SearchLimits::default()
+        };
+        let timeout_ms = allowed_ms + latency.as_millis() as u64 + 1;
+        let search = engine.search(&start_fen, &uci_moves, &limits, timeout_ms, &mut on_info);
+        let search_result = tokio::select! {
+            result = search => result,
+            control = wait_for_control(&mut control, human == Some(side)) => {
+                if control == MatchControl::Abort {
+                    set_result(&match_id, "*".to_string(), ResultReason::Aborted, &matches, &events).await;
+                    break;
+                }
+                // A human's turn is only a wait for their move, so it is cut short by a
+                // pause and asked again afterwards with the time used so far charged.
+                let elapsed_ms = start.elapsed().as_millis() as u64;
+                match side {
+                    Side::White => white_ms = white_ms.saturating_sub(elapsed_ms),
+                    Side::Black => black_ms = black_ms.saturating_sub(elapsed_ms),
+                }
+                continue;
+            }
+        };
+        let bestmove = match search_result {
             Ok(bestmove) => bestmove,
             Err(UciError::Timeout(_)) => {
-                finish_match(&match_id, side, ResultReason::Timeout, &matches).await;
+                finish_match(&match_id, side, ResultReason::Timeout, &matches, &events).await;
+                break;
+            }
+            // The engine is killed when it fails to quit below.
+            Err(UciError::Stalled) => {
+                finish_match(&match_id, side, ResultReason::Stalled, &matches, &events).await;
+                break;
+            }
+            Err(err @ (UciError::UnexpectedEof | UciError::Io(_))) => {
+                // A closed pipe is a crash if the process is gone; otherwise it is ours.
+                let Some(status) = engine.exit_status().await else {
+                    return Err(err);
+                };
+                let (engine_id, transcript) = match side {
+                    Side::White => (&white.id, &transcripts.white),
+                    Side::Black => (&black.id, &transcripts.black),
+                };
+                let crash = crash_report(side, engine_id, status, transcript);
+                if let Some(entry) = matches.write().await.get_mut(&match_id) {
+                    entry.crash = Some(crash);
+                }
+                finish_match(&match_id, side, ResultReason::Crash, &matches, &events).await;
                 break;
             }
             Err(err) => return Err(err),
         };
 
-        let elapsed_ms = start.elapsed().as_millis() as u64;
+        let elapsed_ms = start.elapsed().saturating_sub(latency).as_millis() as u64;
+        if elapsed_ms > allowed_ms {
+            finish_match(&match_id, side, ResultReason::Timeout, &matches, &events).await;
+            break;
+        }
         match side {
             Side::White => white_ms = white_ms.saturating_sub(elapsed_ms),
             Side::Black => black_ms = black_ms.saturating_sub(elapsed_ms),
         }
 
+        if bestmove == "resign" {
+            finish_match(&match_id, side, ResultReason::Resignation, &matches, &events).await;
+            break;
+        }
+
         if bestmove == "(none)" {
             if let Some(outcome) = pos.outcome() {
-                finish_with_outcome(&match_id, outcome, &pos, &matches).await;
+                finish_with_outcome(&match_id, outcome, &pos, &matches, &events).await;
             } else {
-                finish_match(&match_id, side, ResultReason::Error, &matches).await;
+                finish_match(&match_id, side, ResultReason::Error, &matches, &events).await;
             }
             break;
         }
@@ -102,76 +348,178 @@ async fn run_match_inner(
         let mv = match uci_move.to_move(&pos) {
             Ok(mv) => mv,
             Err(_) => {
-                finish_match(&match_id, side, ResultReason::Illegal, &matches).await;
+                finish_match(&match_id, side, ResultReason::Illegal, &matches, &events).await;
                 break;
             }
         };
 
+        let move_number = pos.fullmoves().get();
         let san = San::from_move(&pos, &mv).to_string();
         let pos_next = match pos.play(&mv) {
             Ok(pos_next) => pos_next,
             Err(_) => {
-                finish_match(&match_id, side, ResultReason::Illegal, &matches).await;
+                finish_match(&match_id, side, ResultReason::Illegal, &matches, &events).await;
                 break;
             }
         };
 
+        // The increment is only earned by completing a legal move.
+        match side {
+            Side::White => white_ms += time_control.increment_ms,
+            Side::Black => black_ms += time_control.increment_ms,
+        }
+
         ply += 1;
         moves.push(san.clone());
-        let pgn = format_pgn(&moves);
+        // Engines may write castling either way; pass it on in the variant's notation.
+        let uci = UciMove::from_move(&mv, variant.castling_mode()).to_string();
+        uci_moves.push(uci.clone());
+        let pgn = format_movetext(&moves, first_move_number, black_first);
         let fen_next = Fen::from_position(pos_next.clone(), EnPassantMode::Legal).to_string();
 
-        update_match_state(
-            &matches,
-            &match_id,
+        let snapshot = MoveSnapshot {
             ply,
-            &fen_next,
-            &pgn,
-            white_ms,
-            black_ms,
-            bestmove.clone(),
+            uci,
             san,
-        )
-        .await;
+            fen: fen_next,
+            pgn,
+            stats: MoveStats {
+                score: main_line.score,
+                depth: main_line.depth,
+                seldepth: main_line.seldepth,
+                nodes: main_line.nodes,
+                nps: main_line.nps,
+                time_ms: elapsed_ms,
+                clock_ms: match side {
+                    Side::White => white_ms,
+                    Side::Black => black_ms,
+                },
+                overrun_ms: elapsed_ms.saturating_sub(remaining_ms),
+            },
+        };
+        // Like cutechess, bound scores are not trusted for adjudication.
+        let verdict = adjudicator.record(side, main_line.score.filter(|_| !main_line.bound), move_number);
+        update_match_state(&matches, &match_id, snapshot.clone(), Clock { white_ms, black_ms }).await;
+        let _ = events.send(MatchEvent::Move(MoveEvent::from(snapshot)));
 
         pos = pos_next;
 
         if let Some(outcome) = pos.outcome() {
-            finish_with_outcome(&match_id, outcome, &pos, &matches).await;
+            finish_with_outcome(&match_id, outcome, &pos, &matches, &events).await;
+            break;
+        }
+        let tablebase_verdict = match (&pos, &tablebases) {
+            (VariantPosition::Chess(chess), Some(tables)) => tables.probe(chess),
+            _ => None,
+        };
+        if let Some(verdict) = tablebase_verdict {
+            set_result(&match_id, verdict.result().to_string(), ResultReason::Tablebase, &matches, &events).await;
+            break;
+        }
+        if let Some(verdict) = verdict {
+            set_result(&match_id, verdict.result().to_string(), ResultReason::Adjudication, &matches, &events).await;
+            break;
+        }
+        if adjudication.max_plies.is_some_and(|max_plies| ply >= max_plies) {
+            set_result(&match_id, "1/2-1/2".to_string(), ResultReason::MoveLimit, &matches, &events).await;
             break;
         }
     }
 
-    let _ = white_engine.quit().await;
-    let _ = black_engine.quit().await;
+    white_engine.quit().await;
+    black_engine.quit().await;
 
     Ok(())
 }
 
+/// Keeps the latest scored report of the best line, which supplies the move's stats and
+/// the score adjudication sees. Progress lines without a score (`currmove`, a bare
+/// `depth`) would otherwise blank them just before `bestmove`.
+fn record_main_line(main_line: &mut SearchInfo, info: &SearchInfo) {
+    if info.multipv.unwrap_or(1) == 1 && info.score.is_some() {
+        *main_line = info.clone();
+    }
+}
+
+/// The fastest of a few readiness round trips, taken as the engine's communication
+/// latency. Engines without a ping command measure as zero.
+async fn measure_latency(engine: &mut dyn EngineProtocol) -> Duration {
+    let mut fastest = Duration::MAX;
+    for _ in 0..LATENCY_PINGS {
+        let start = Instant::now();
+        if engine.is_ready().await.is_err() {
+            return Duration::ZERO;
+        }
+        fastest = fastest.min(start.elapsed());
+    }
+    fastest
+}
+
+fn crash_report(side: Side, engine_id: &str, status: ExitStatus, transcript: &Transcript) -> CrashReport {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal = None;
+
+    CrashReport {
+        side,
+        engine_id: engine_id.to_string(),
+        exit_code: status.code(),
+        signal,
+        stderr: transcript.stderr_tail(CRASH_STDERR_LINES),
+    }
+}
+
+async fn wait_while_paused(control: &mut watch::Receiver<MatchControl>) -> MatchControl {
+    // If the sender is gone nobody can resume the match, so just keep playing.
+    match control.wait_for(|state| *state != MatchControl::Pause).await {
+        Ok(state) => *state,
+        Err(_) => MatchControl::Run,
+    }
+}
+
+/// Waits for an abort during a search, or also for a pause when the search can be
+/// interrupted and asked again.
+async fn wait_for_control(control: &mut watch::Receiver<MatchControl>, pausable: bool) -> MatchControl {
+    let wanted = |state: &MatchControl| *state == MatchControl::Abort || (pausable && *state == MatchControl::Pause);
+    let state = control.wait_for(wanted).await.map(|state| *state);
+    match state {
+        Ok(state) => state,
+        Err(_) => std::future::pending().await,
+    }
+}
+
+/// Pauses the match with its clocks stopped at `clocks`, or resumes it with `None`. Only
+/// the runner does this, between searches, so a paused match never has a clock ticking.
+async fn set_paused(matches: &Arc<RwLock<HashMap<String, MatchState>>>, match_id: &str, clocks: Option<Clock>) {
+    if let Some(entry) = matches.write().await.get_mut(match_id) {
+        match (clocks, entry.status) {
+            (Some(clocks), MatchStatus::Running) => {
+                entry.status = MatchStatus::Paused;
+                entry.running = None;
+                entry.clocks = clocks;
+            }
+            (None, MatchStatus::Paused) => entry.status = MatchStatus::Running,
+            _ => {}
+        }
+    }
+}
+
 async fn update_match_state(
     matches: &Arc<RwLock<HashMap<String, MatchState>>>,
     match_id: &str,
-    ply: u32,
-    fen: &str,
-    pgn: &str,
-    white_ms: u64,
-    black_ms: u64,
-    uci: String,
-    san: String,
+    snapshot: MoveSnapshot,
+    clocks: Clock,
 ) {
     let mut guard = matches.write().await;
     if let Some(entry) = guard.get_mut(match_id) {
-        entry.ply = ply;
-        entry.current_fen = fen.to_string();
-        entry.pgn = pgn.to_string();
-        entry.clocks = Clock { white_ms, black_ms };
-        entry.last_move = Some(crate::domain::MoveSnapshot {
-            ply,
-            uci,
-            san,
-            fen: fen.to_string(),
-            pgn: pgn.to_string(),
-        });
+        entry.ply = snapshot.ply;
+        entry.current_fen = snapshot.fen.clone();
+        entry.side_to_move = Side::to_move(&snapshot.fen);
+        entry.pgn = snapshot.pgn.clone();
+        entry.clocks = clocks;
+        entry.running = None;
+        entry.moves.push(snapshot);
     }
 }
 
@@ -180,29 +528,26 @@ async fn finish_match(
     offender: Side,
     reason: ResultReason,
     matches: &Arc<RwLock<HashMap<String, MatchState>>>,
+    events: &broadcast::Sender<MatchEvent>,
 ) {
     let result = match offender {
         Side::White => "0-1",
         Side::Black => "1-0",
     };
 
-    let mut guard = matches.write().await;
-    if let Some(entry) = guard.get_mut(match_id) {
-        entry.status = MatchStatus::Finished;
-        entry.result = Some(MatchResult {
-            result: result.to_string(),
-            reason,
-        });
-    }
+    set_result(match_id, result.to_string(), reason, matches, events).await;
 }
 
 async fn finish_with_outcome(
     match_id: &str,
     outcome: Outcome,
-    pos: &Chess,
+    pos: &VariantPosition,
     matches: &Arc<RwLock<HashMap<String, MatchState>>>,
+    events: &broadcast::Sender<MatchEvent>,
 ) {
-    let reason = if pos.is_checkmate() {
+    let reason = if pos.variant_outcome().is_some() {
+        ResultReason::Variant
+    } else if pos.is_checkmate() {
         ResultReason::Checkmate
     } else if pos.is_stalemate() {
         ResultReason::Stalemate
@@ -210,27 +555,180 @@ async fn finish_with_outcome(
         ResultReason::Draw
     };
 
-    let result = outcome.as_str().to_string();
-    let mut guard = matches.write().await;
-    if let Some(entry) = guard.get_mut(match_id) {
-        entry.status = MatchStatus::Finished;
-        entry.result = Some(MatchResult { result, reason });
+    set_result(match_id, outcome.as_str().to_string(), reason, matches, events).await;
+}
+
+async fn set_result(
+    match_id: &str,
+    result: String,
+    reason: ResultReason,
+    matches: &Arc<RwLock<HashMap<String, MatchState>>>,
+    events: &broadcast::Sender<MatchEvent>,
+) {
+    {
+        let mut guard = matches.write().await;
+        if let Some(entry) = guard.get_mut(match_id) {
+            entry.status = MatchStatus::Finished;
+            entry.running = None;
+            entry.result = Some(MatchResult {
+                result: result.clone(),
+                reason,
+            });
+        }
     }
+
+    // Publish only after the shared state is final, so a subscriber that misses the
+    // event is guaranteed to see the result when it reads the state.
+    let _ = events.send(MatchEvent::Result(ResultEvent { result, reason }));
 }
 
-fn format_pgn(moves: &[String]) -> String {
-    let mut pgn = String::new();
-    for (idx, san) in moves.iter().enumerate() {
-        if idx % 2 == 0 {
-            if !pgn.is_empty() {
-                pgn.push(' ');
-            }
-            let move_no = idx / 2 + 1;
-            pgn.push_str(&format!("{move_no}. {san}"));
-        } else {
-            pgn.push(' ');
-            pgn.push_str(san);
+/// The usual start position of `variant`. Chess960 has no single one and gets the
+/// standard position here.
+pub fn initial_fen(variant: Variant) -> String {
+    Fen::from_position(VariantPosition::new(variant.rules()), EnPassantMode::Legal).to_string()
+}
+
+/// Parses a FEN (or the first four EPD fields) into a standard chess position.
+pub fn start_position(fen: &str) -> Result<Chess, String> {
+    parse_fen(fen)?.into_position(CastlingMode::Standard).map_err(|err| format!("illegal position {fen}: {err}"))
+}
+
+/// Like [`start_position`], for a position of `variant`: castling rights are read as the
+/// variant plays them, and crazyhouse pockets and three-check counters are kept.
+pub fn start_position_for(fen: &str, variant: Variant) -> Result<VariantPosition, String> {
+    let setup = parse_fen(fen)?.into_setup();
+    VariantPosition::from_setup(variant.rules(), setup, variant.castling_mode())
+        .map_err(|err| format!("illegal position {fen}: {err}"))
+}
+
+fn parse_fen(fen: &str) -> Result<Fen, String> {
+    fen.parse().map_err(|err| format!("invalid fen {fen}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Protocol;
+    use crate::uci::{Score, parse_info};
+
+    /// A UCI engine in a shell script that waits `think` before every `bestmove`, playing
+    /// `e2e4` as white and `e7e5` as black.
+    #[cfg(unix)]
+    fn sleeping_engine(id: &str, think: &str) -> EngineSpec {
+        let script = format!(
+            r#"while read cmd rest; do
+                case $cmd in
+                    uci) echo uciok ;;
+                    isready) echo readyok ;;
+                    position) set -- $rest; words=$# ;;
+                    go) sleep {think}; if [ $words = 7 ]; then echo "bestmove e2e4"; else echo "bestmove e7e5"; fi ;;
+                    quit) exit ;;
+                esac
+            done"#
+        );
+        EngineSpec {
+            id: id.to_string(),
+            name: id.to_string(),
+            author: String::new(),
+            path: "sh".into(),
+            args: vec!["-c".to_string(), script],
+            working_dir: None,
+            protocol: Protocol::Uci,
+            options: Vec::new(),
+        }
+    }
+
+    /// White overruns its 100 ms clock by about 200 ms on the first move.
+    #[cfg(unix)]
+    async fn play_overrun(timemargin_ms: u64) -> MatchState {
+        let config = MatchConfig {
+            white: sleeping_engine("slow", "0.3"),
+            black: sleeping_engine("fast", "0"),
+            time_control: TimeControl { initial_ms: 100, increment_ms: 0 },
+            variant: Variant::Standard,
+            start_fen: None,
+            transcripts: MatchTranscripts::default(),
+            stall_timeout_ms: 0,
+            timemargin_ms,
+            adjudication: AdjudicationRules { max_plies: Some(2), ..AdjudicationRules::default() },
+            tablebases: None,
+            human: None,
+        };
+        play_game("overrun".to_string(), config).await
+    }
+
+    #[test]
+    fn keeps_pockets_and_check_counters_in_fens() {
+        let crazyhouse = "rnbqkbnr/ppp1pppp/8/3P4/8/8/PPPP1PPP/RNBQKBNR[P] b KQkq - 0 2";
+        let pos = start_position_for(crazyhouse, Variant::Crazyhouse).expect("crazyhouse position");
+        assert_eq!(Fen::from_position(pos, EnPassantMode::Legal).to_string(), crazyhouse);
+
+        let pos = start_position_for(&initial_fen(Variant::ThreeCheck), Variant::ThreeCheck).expect("3check position");
+        assert_eq!(
+            Fen::from_position(pos, EnPassantMode::Legal).to_string(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1"
+        );
+
+        assert!(initial_fen(Variant::Horde).starts_with("rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP"));
+    }
+
+    #[test]
+    fn detects_variant_wins() {
+        let hill = start_position_for("4k3/8/8/3K4/8/8/8/8 b - - 0 1", Variant::KingOfTheHill).expect("koth position");
+        assert_eq!(hill.variant_outcome(), Some(Outcome::Decisive { winner: Color::White }));
+
+        let same_as_chess = start_position_for("4k3/8/8/3K4/8/8/8/8 b - - 0 1", Variant::Standard).expect("position");
+        assert_eq!(same_as_chess.variant_outcome(), None);
+    }
+
+    #[test]
+    fn progress_lines_do_not_replace_the_scored_main_line() {
+        let mut main_line = SearchInfo::default();
+        for line in [
+            "info depth 12 seldepth 18 multipv 1 score cp 35 nodes 90000 nps 450000 pv e2e4 e7e5",
+            "info depth 12 multipv 2 score cp 20 pv d2d4",
+            "info depth 13 currmove g1f3 currmovenumber 2",
+        ] {
+            record_main_line(&mut main_line, &parse_info(line).expect("info line"));
         }
+
+        assert_eq!(main_line.score, Some(Score::Cp(35)));
+        assert_eq!((main_line.depth, main_line.nodes), (Some(12), Some(90000)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn overruns_within_the_time_margin_are_not_forfeits() {
+        let saved = play_overrun(1_000).await;
+        let result = saved.result.expect("result");
+        assert_eq!((result.result.as_str(), result.reason), ("1/2-1/2", ResultReason::MoveLimit));
+        let first = &saved.moves[0].stats;
+        assert_eq!(first.clock_ms, 0);
+        assert!(first.overrun_ms >= 150, "overrun of {} ms", first.overrun_ms);
+
+        let forfeited = play_overrun(50).await.result.expect("result");
+        assert_eq!((forfeited.result.as_str(), forfeited.reason), ("0-1", ResultReason::Timeout));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn games_are_drawn_once_they_reach_the_move_limit() {
+        let config = MatchConfig {
+            white: sleeping_engine("white", "0"),
+            black: sleeping_engine("black", "0"),
+            time_control: TimeControl { initial_ms: 60_000, increment_ms: 0 },
+            variant: Variant::Standard,
+            start_fen: None,
+            transcripts: MatchTranscripts::default(),
+            stall_timeout_ms: 0,
+            timemargin_ms: 0,
+            adjudication: AdjudicationRules { max_plies: Some(1), ..AdjudicationRules::default() },
+            tablebases: None,
+            human: None,
+        };
+        let saved = play_game("limit".to_string(), config).await;
+        let result = saved.result.expect("result");
+        assert_eq!((result.result.as_str(), result.reason), ("1/2-1/2", ResultReason::MoveLimit));
+        assert_eq!(saved.moves.len(), 1);
     }
-    pgn
 }
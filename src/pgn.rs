@@ -0,0 +1,196 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::domain::{MatchState, ResultReason, TimeControl, Variant};
+
+/// Tag values describing where a finished game was played.
+pub struct GameInfo<'a> {
+    pub event: &'a str,
+    pub round: u32,
+    pub white: &'a str,
+    pub black: &'a str,
+    pub time_control: TimeControl,
+}
+
+/// Formats SAN moves as PGN movetext, numbering from `first_move_number`. When the game
+/// starts with black to move, the first move is written as `N... san`.
+pub fn format_movetext(moves: &[String], first_move_number: u32, black_first: bool) -> String {
+    let mut pgn = String::new();
+    let offset = usize::from(black_first);
+    for (idx, san) in moves.iter().enumerate() {
+        let half = idx + offset;
+        let move_no = first_move_number as usize + half / 2;
+        if !pgn.is_empty() {
+            pgn.push(' ');
+        }
+        if half % 2 == 0 {
+            pgn.push_str(&format!("{move_no}. {san}"));
+        } else if idx == 0 {
+            pgn.push_str(&format!("{move_no}... {san}"));
+        } else {
+            pgn.push_str(san);
+        }
+    }
+    pgn
+}
+
+/// A move with the annotations written after it in PGN movetext.
+pub struct AnnotatedMove {
+    pub san: String,
+    /// Numeric annotation glyph, written as `$n`.
+    pub nag: Option<u8>,
+    pub comment: Option<String>,
+    /// A complete alternative line, e.g. `12... Bd7`, written in parentheses.
+    pub variation: Option<String>,
+}
+
+/// Like [`format_movetext`], with NAGs, comments and variations after each move. A black
+/// move that follows a comment or variation repeats its move number as `N...`.
+pub fn format_annotated_movetext(moves: &[AnnotatedMove], first_move_number: u32, black_first: bool) -> String {
+    let mut tokens = Vec::new();
+    let offset = usize::from(black_first);
+    let mut interrupted = true;
+    for (idx, mv) in moves.iter().enumerate() {
+        let half = idx + offset;
+        let move_no = first_move_number as usize + half / 2;
+        if half % 2 == 0 {
+            tokens.push(format!("{move_no}. {}", mv.san));
+        } else if interrupted {
+            tokens.push(format!("{move_no}... {}", mv.san));
+        } else {
+            tokens.push(mv.san.clone());
+        }
+
+        if let Some(nag) = mv.nag {
+            tokens.push(format!("${nag}"));
+        }
+        if let Some(comment) = &mv.comment {
+            tokens.push(format!("{{ {comment} }}"));
+        }
+        if let Some(variation) = &mv.variation {
+            tokens.push(format!("({variation})"));
+        }
+        interrupted = mv.comment.is_some() || mv.variation.is_some();
+    }
+    tokens.join(" ")
+}
+
+/// Assembles a complete PGN game from its tag pairs, movetext and result.
+pub fn format_game(tags: &[(&str, String)], movetext: &str, result: &str) -> String {
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+    }
+    pgn.push('\n');
+    if movetext.is_empty() {
+        pgn.push_str(result);
+    } else {
+        pgn.push_str(&format!("{movetext} {result}"));
+    }
+    pgn.push_str("\n\n");
+    pgn
+}
+
+/// Full PGN for a finished match, with the seven-tag roster plus time control,
+/// termination and the starting position when it is not the standard one.
+pub fn game_record(info: &GameInfo<'_>, state: &MatchState, movetext: &str, standard_start: bool) -> String {
+    let result = state.result.as_ref().map(|r| r.result.clone()).unwrap_or_else(|| "*".to_string());
+    let mut tags = vec![
+        ("Event", info.event.to_string()),
+        ("Site", "?".to_string()),
+        ("Date", today()),
+        ("Round", info.round.to_string()),
+        ("White", info.white.to_string()),
+        ("Black", info.black.to_string()),
+        ("Result", result.clone()),
+        ("TimeControl", info.time_control.to_string()),
+    ];
+    if state.variant != Variant::Standard {
+        tags.push(("Variant", state.variant.pgn_name().to_string()));
+    }
+    if !standard_start || state.variant != Variant::Standard {
+        tags.push(("SetUp", "1".to_string()));
+        tags.push(("FEN", state.start_fen.clone()));
+    }
+    if let Some(reason) = state.result.as_ref().map(|r| r.reason) {
+        tags.push(("Termination", termination(reason).to_string()));
+    }
+
+    format_game(&tags, movetext, &result)
+}
+
+/// PGN `Termination` tag value for a result reason.
+pub fn termination(reason: ResultReason) -> &'static str {
+    match reason {
+        ResultReason::Checkmate | ResultReason::Stalemate | ResultReason::Draw | ResultReason::Variant => "normal",
+        ResultReason::Resignation => "normal",
+        ResultReason::Timeout => "time forfeit",
+        ResultReason::Illegal => "rules infraction",
+        ResultReason::Aborted | ResultReason::Crash => "abandoned",
+        ResultReason::Stalled => "stalled connection",
+        ResultReason::Adjudication | ResultReason::Tablebase | ResultReason::MoveLimit => "adjudication",
+        ResultReason::Error => "unterminated",
+    }
+}
+
+/// Today's UTC date in PGN `Date` tag format (`YYYY.MM.DD`).
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+
+    // Civil-from-days, after Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sans(moves: &[&str]) -> Vec<String> {
+        moves.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn formats_movetext_from_start_position() {
+        let movetext = format_movetext(&sans(&["e4", "e5", "Nf3"]), 1, false);
+        assert_eq!(movetext, "1. e4 e5 2. Nf3");
+    }
+
+    #[test]
+    fn formats_movetext_starting_with_black() {
+        let movetext = format_movetext(&sans(&["e5", "Nf3", "Nc6"]), 7, true);
+        assert_eq!(movetext, "7... e5 8. Nf3 Nc6");
+    }
+
+    #[test]
+    fn formats_annotated_movetext() {
+        let annotated = |san: &str, nag: Option<u8>, variation: Option<&str>| AnnotatedMove {
+            san: san.to_string(),
+            nag,
+            comment: None,
+            variation: variation.map(str::to_string),
+        };
+        let moves =
+            [annotated("e4", None, None), annotated("e5", None, None), annotated("Qh5", Some(6), Some("2. Nf3")), annotated("Nc6", None, None)];
+        assert_eq!(format_annotated_movetext(&moves, 1, false), "1. e4 e5 2. Qh5 $6 (2. Nf3) 2... Nc6");
+    }
+
+    #[test]
+    fn formats_game_with_tags() {
+        let tags = [("White", "A".to_string()), ("Black", "B \"dev\"".to_string())];
+        let game = format_game(&tags, "1. e4 e5", "1/2-1/2");
+        assert_eq!(game, "[White \"A\"]\n[Black \"B \\\"dev\\\"\"]\n\n1. e4 e5 1/2-1/2\n\n");
+    }
+}
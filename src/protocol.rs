@@ -0,0 +1,164 @@
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+
+use crate::config::Protocol;
+use crate::domain::TimeControl;
+use crate::engine::EngineSpec;
+use crate::transcript::Transcript;
+use crate::uci::{SearchInfo, SearchLimits, UciEngineInfo, UciError, UciProcess};
+use crate::xboard::XboardProcess;
+
+/// What the match runner and the analysis commands need from an engine, whatever protocol
+/// it speaks. Positions are always given as a start FEN plus the UCI moves played from it.
+pub trait EngineProtocol: Send {
+    /// Identifies the engine and collects the options it advertises.
+    fn handshake(&mut self) -> BoxFuture<'_, Result<UciEngineInfo, UciError>>;
+
+    /// Sends configured options. Call after the handshake and before `is_ready`.
+    fn configure<'a>(&'a mut self, options: &'a [(String, String)]) -> BoxFuture<'a, Result<(), UciError>>;
+
+    fn is_ready(&mut self) -> BoxFuture<'_, Result<(), UciError>>;
+
+    /// Prepares a new game, optionally announcing its time control.
+    fn new_game(&mut self, time_control: Option<TimeControl>) -> BoxFuture<'_, Result<(), UciError>>;
+
+    /// Searches the position reached by `moves` from `start_fen` and returns the chosen
+    /// move in UCI notation, `(none)` without legal moves, or `resign`.
+    fn search<'a>(
+        &'a mut self,
+        start_fen: &'a str,
+        moves: &'a [String],
+        limits: &'a SearchLimits,
+        timeout_ms: u64,
+        on_info: &'a mut (dyn FnMut(SearchInfo) + Send),
+    ) -> BoxFuture<'a, Result<String, UciError>>;
+
+    /// Enables the stall watchdog for searches; see `EngineProcess::read_search_line`.
+    fn set_stall_timeout(&mut self, stall_timeout: Option<Duration>);
+
+    /// The exit status once the process has died; `None` while it is still running.
+    fn exit_status(&mut self) -> BoxFuture<'_, Option<ExitStatus>>;
+
+    fn quit(self: Box<Self>) -> BoxFuture<'static, ()>;
+}
+
+/// Starts an engine process with the driver for `protocol`.
+pub async fn spawn_engine(
+    protocol: Protocol,
+    path: &PathBuf,
+    args: &[String],
+    working_dir: Option<&PathBuf>,
+    transcript: Transcript,
+) -> Result<Box<dyn EngineProtocol>, UciError> {
+    Ok(match protocol {
+        Protocol::Uci => Box::new(UciProcess::spawn(path, args, working_dir, transcript).await?),
+        Protocol::Xboard => Box::new(XboardProcess::spawn(path, args, working_dir, transcript).await?),
+    })
+}
+
+/// Starts a discovered engine, recording its traffic in `transcript`.
+pub async fn spawn_spec(engine: &EngineSpec, transcript: Transcript) -> Result<Box<dyn EngineProtocol>, UciError> {
+    spawn_engine(engine.protocol, &engine.path, &engine.args, engine.working_dir.as_ref(), transcript).await
+}
+
+impl EngineProtocol for UciProcess {
+    fn handshake(&mut self) -> BoxFuture<'_, Result<UciEngineInfo, UciError>> {
+        Box::pin(UciProcess::handshake(self))
+    }
+
+    fn configure<'a>(&'a mut self, options: &'a [(String, String)]) -> BoxFuture<'a, Result<(), UciError>> {
+        Box::pin(UciProcess::configure(self, options))
+    }
+
+    fn is_ready(&mut self) -> BoxFuture<'_, Result<(), UciError>> {
+        Box::pin(UciProcess::is_ready(self))
+    }
+
+    fn new_game(&mut self, _time_control: Option<TimeControl>) -> BoxFuture<'_, Result<(), UciError>> {
+        Box::pin(async move {
+            self.ucinewgame().await?;
+            UciProcess::is_ready(self).await
+        })
+    }
+
+    fn search<'a>(
+        &'a mut self,
+        start_fen: &'a str,
+        moves: &'a [String],
+        limits: &'a SearchLimits,
+        timeout_ms: u64,
+        on_info: &'a mut (dyn FnMut(SearchInfo) + Send),
+    ) -> BoxFuture<'a, Result<String, UciError>> {
+        Box::pin(async move {
+            self.send_line(&position_command(start_fen, moves)).await?;
+            self.bestmove(limits, timeout_ms, on_info).await
+        })
+    }
+
+    fn set_stall_timeout(&mut self, stall_timeout: Option<Duration>) {
+        UciProcess::set_stall_timeout(self, stall_timeout);
+    }
+
+    fn exit_status(&mut self) -> BoxFuture<'_, Option<ExitStatus>> {
+        Box::pin(UciProcess::exit_status(self))
+    }
+
+    fn quit(self: Box<Self>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let _ = UciProcess::quit(*self).await;
+        })
+    }
+}
+
+impl EngineProtocol for XboardProcess {
+    fn handshake(&mut self) -> BoxFuture<'_, Result<UciEngineInfo, UciError>> {
+        Box::pin(XboardProcess::handshake(self))
+    }
+
+    fn configure<'a>(&'a mut self, options: &'a [(String, String)]) -> BoxFuture<'a, Result<(), UciError>> {
+        Box::pin(XboardProcess::configure(self, options))
+    }
+
+    fn is_ready(&mut self) -> BoxFuture<'_, Result<(), UciError>> {
+        Box::pin(XboardProcess::is_ready(self))
+    }
+
+    fn new_game(&mut self, time_control: Option<TimeControl>) -> BoxFuture<'_, Result<(), UciError>> {
+        Box::pin(XboardProcess::new_game(self, time_control))
+    }
+
+    fn search<'a>(
+        &'a mut self,
+        start_fen: &'a str,
+        moves: &'a [String],
+        limits: &'a SearchLimits,
+        timeout_ms: u64,
+        on_info: &'a mut (dyn FnMut(SearchInfo) + Send),
+    ) -> BoxFuture<'a, Result<String, UciError>> {
+        Box::pin(XboardProcess::search(self, start_fen, moves, limits, timeout_ms, on_info))
+    }
+
+    fn set_stall_timeout(&mut self, stall_timeout: Option<Duration>) {
+        XboardProcess::set_stall_timeout(self, stall_timeout);
+    }
+
+    fn exit_status(&mut self) -> BoxFuture<'_, Option<ExitStatus>> {
+        Box::pin(XboardProcess::exit_status(self))
+    }
+
+    fn quit(self: Box<Self>) -> BoxFuture<'static, ()> {
+        Box::pin(XboardProcess::quit(*self))
+    }
+}
+
+/// The UCI `position` command for a start FEN and the moves played from it.
+pub fn position_command(start_fen: &str, moves: &[String]) -> String {
+    if moves.is_empty() {
+        format!("position fen {start_fen}")
+    } else {
+        format!("position fen {start_fen} moves {}", moves.join(" "))
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use shakmaty::san::San;
+use shakmaty::uci::UciMove;
+use shakmaty::variant::VariantPosition;
+use shakmaty::{Color, Move, Position};
+
+use crate::bench::BenchLimit;
+use crate::domain::{MoveSnapshot, Variant};
+use crate::engine::EngineSpec;
+use crate::match_runner::start_position_for;
+use crate::pgn::{AnnotatedMove, format_annotated_movetext, format_movetext};
+use crate::protocol::spawn_spec;
+use crate::transcript::Transcript;
+use crate::uci::{MATE_CP, PvLines, Score};
+
+pub const DEFAULT_REVIEW_DEPTH: u32 = 12;
+
+/// Evals beyond this are clamped before computing losses, so that a won position staying
+/// won is not flagged.
+const LOSS_CLAMP_CP: i32 = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Classification {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    /// Classifies a move by how many centipawns it lost for the side that played it.
+    pub fn from_loss(loss_cp: i32) -> Option<Self> {
+        match loss_cp {
+            300.. => Some(Classification::Blunder),
+            100..=299 => Some(Classification::Mistake),
+            50..=99 => Some(Classification::Inaccuracy),
+            _ => None,
+        }
+    }
+
+    /// PGN numeric annotation glyph: `$6` (?!), `$2` (?), `$4` (??).
+    pub fn nag(self) -> u8 {
+        match self {
+            Classification::Inaccuracy => 6,
+            Classification::Mistake => 2,
+            Classification::Blunder => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewedMove {
+    pub ply: u32,
+    pub uci: String,
+    pub san: String,
+    /// Evals from white's point of view before and after the move.
+    pub eval_before: i32,
+    pub eval_after: i32,
+    /// Centipawns lost by the side that moved.
+    pub loss: i32,
+    pub classification: Option<Classification>,
+    /// The engine's preferred move when the played move was classified.
+    pub best: Option<String>,
+}
+
+/// Engine review of a finished game.
+#[derive(Debug, Clone, Serialize)]
+pub struct GameReview {
+    pub engine_id: String,
+    pub depth: u32,
+    /// Eval of every position from white's point of view, starting with the start
+    /// position. Mates count as ±10000 centipawns.
+    pub evals: Vec<i32>,
+    pub moves: Vec<ReviewedMove>,
+    /// Movetext with eval comments, NAGs and the better alternative for flagged moves.
+    pub pgn: String,
+}
+
+/// Engine verdict on one position: white-relative eval and the move the engine chose.
+struct PositionEval {
+    eval: i32,
+    best: Option<Move>,
+    best_san: Option<String>,
+}
+
+/// Searches every position of the game to `depth` with one engine process and classifies
+/// each move by the eval it gave away.
+pub async fn review_game(
+    engine: &EngineSpec,
+    start_fen: &str,
+    variant: Variant,
+    moves: &[MoveSnapshot],
+    depth: u32,
+) -> Result<GameReview, String> {
+    let failed = |err| format!("{}: {err}", engine.id);
+
+    let mut pos = start_position_for(start_fen, variant)?;
+    let first_move_number = pos.fullmoves().get();
+    let black_first = pos.turn() == Color::Black;
+
+    let mut process = spawn_spec(engine, Transcript::default()).await.map_err(failed)?;
+    process.handshake().await.map_err(failed)?;
+    process.configure(&engine.options_for(variant)).await.map_err(failed)?;
+    process.new_game(None).await.map_err(failed)?;
+
+    let limit = BenchLimit::Depth(depth);
+    let mut played = Vec::new();
+    let mut played_moves = Vec::new();
+    let mut evals = Vec::new();
+    for idx in 0..=moves.len() {
+        let verdict = if pos.is_game_over() {
+            PositionEval { eval: terminal_eval(&pos), best: None, best_san: None }
+        } else {
+            let mut lines = PvLines::default();
+            let bestmove = process
+                .search(start_fen, &played, &limit.search_limits(), limit.timeout_ms(), &mut |info| lines.update(&info))
+                .await
+                .map_err(failed)?;
+            let score = lines.top(1).first().and_then(|(_, info)| info.score);
+            let best = bestmove.parse::<UciMove>().ok().and_then(|uci| uci.to_move(&pos).ok());
+            let best_san = best.as_ref().map(|mv| San::from_move(&pos, mv).to_string());
+            PositionEval { eval: white_eval(score, pos.turn()), best, best_san }
+        };
+        evals.push(verdict);
+
+        let Some(snapshot) = moves.get(idx) else {
+            break;
+        };
+        let mv = snapshot
+            .uci
+            .parse::<UciMove>()
+            .ok()
+            .and_then(|uci| uci.to_move(&pos).ok())
+            .ok_or_else(|| format!("illegal move {} in stored game", snapshot.uci))?;
+        pos.play_unchecked(&mv);
+        played.push(snapshot.uci.clone());
+        played_moves.push(mv);
+    }
+    process.quit().await;
+
+    let mut reviewed = Vec::new();
+    for (idx, snapshot) in moves.iter().enumerate() {
+        let white_moved = (idx + usize::from(black_first)) % 2 == 0;
+        let before = &evals[idx];
+        let after = &evals[idx + 1];
+        let loss = move_loss(before.eval, after.eval, white_moved);
+        let classification =
+            Classification::from_loss(loss).filter(|_| before.best.as_ref() != Some(&played_moves[idx]));
+
+        reviewed.push(ReviewedMove {
+            ply: snapshot.ply,
+            uci: snapshot.uci.clone(),
+            san: snapshot.san.clone(),
+            eval_before: before.eval,
+            eval_after: after.eval,
+            loss,
+            classification,
+            best: classification.and(before.best_san.clone()),
+        });
+    }
+
+    let pgn = annotated_movetext(&reviewed, first_move_number, black_first);
+    Ok(GameReview {
+        engine_id: engine.id.clone(),
+        depth,
+        evals: evals.iter().map(|verdict| verdict.eval).collect(),
+        moves: reviewed,
+        pgn,
+    })
+}
+
+/// Converts an engine score (side to move's point of view) to white-relative centipawns.
+fn white_eval(score: Option<Score>, turn: Color) -> i32 {
+    let cp = score.map_or(0, Score::centipawns);
+    if turn == Color::White { cp } else { -cp }
+}
+
+fn terminal_eval(pos: &VariantPosition) -> i32 {
+    match pos.outcome().and_then(|outcome| outcome.winner()) {
+        Some(Color::White) => MATE_CP,
+        Some(Color::Black) => -MATE_CP,
+        None => 0,
+    }
+}
+
+fn move_loss(before: i32, after: i32, white_moved: bool) -> i32 {
+    let clamp = |eval: i32| eval.clamp(-LOSS_CLAMP_CP, LOSS_CLAMP_CP);
+    let loss = clamp(before) - clamp(after);
+    if white_moved { loss.max(0) } else { (-loss).max(0) }
+}
+
+fn annotated_movetext(moves: &[ReviewedMove], first_move_number: u32, black_first: bool) -> String {
+    let annotated: Vec<AnnotatedMove> = moves
+        .iter()
+        .enumerate()
+        .map(|(idx, mv)| {
+            let half = idx + usize::from(black_first);
+            let move_number = first_move_number + (half / 2) as u32;
+            AnnotatedMove {
+                san: mv.san.clone(),
+                nag: mv.classification.map(Classification::nag),
+                comment: Some(format!("[%eval {}]", format_eval(mv.eval_after))),
+                variation: mv
+                    .best
+                    .as_ref()
+                    .map(|best| format_movetext(std::slice::from_ref(best), move_number, half % 2 == 1)),
+            }
+        })
+        .collect();
+    format_annotated_movetext(&annotated, first_move_number, black_first)
+}
+
+/// Eval in pawns as used by `[%eval]` comments: `0.35`, `-1.20`, or `#3` / `#-2` for mates.
+fn format_eval(eval: i32) -> String {
+    if eval.abs() > MATE_CP - 1_000 {
+        let moves = MATE_CP - eval.abs();
+        if eval > 0 { format!("#{moves}") } else { format!("#-{moves}") }
+    } else {
+        format!("{:.2}", f64::from(eval) / 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_scores_to_white_point_of_view() {
+        assert_eq!(white_eval(Some(Score::Cp(40)), Color::White), 40);
+        assert_eq!(white_eval(Some(Score::Cp(40)), Color::Black), -40);
+        assert_eq!(white_eval(Some(Score::Mate(3)), Color::Black), -9_997);
+        assert_eq!(white_eval(Some(Score::Mate(-2)), Color::White), -9_998);
+        assert_eq!(format_eval(-9_998), "#-2");
+        assert_eq!(format_eval(35), "0.35");
+    }
+
+    #[test]
+    fn classifies_losses_for_the_side_that_moved() {
+        assert_eq!(move_loss(50, -300, true), 350);
+        assert_eq!(move_loss(50, -300, false), 0);
+        assert_eq!(move_loss(-20, 60, false), 80);
+        // Already winning by more than the clamp: no loss.
+        assert_eq!(move_loss(2_500, 1_200, true), 0);
+
+        assert_eq!(Classification::from_loss(350), Some(Classification::Blunder));
+        assert_eq!(Classification::from_loss(120), Some(Classification::Mistake));
+        assert_eq!(Classification::from_loss(80), Some(Classification::Inaccuracy));
+        assert_eq!(Classification::from_loss(20), None);
+    }
+
+    #[test]
+    fn annotates_movetext_with_nags_and_alternatives() {
+        let reviewed =
+            |san: &str, eval_after: i32, classification: Option<Classification>, best: Option<&str>| ReviewedMove {
+                ply: 0,
+                uci: String::new(),
+                san: san.to_string(),
+                eval_before: 0,
+                eval_after,
+                loss: 0,
+                classification,
+                best: best.map(str::to_string),
+            };
+        let moves = vec![
+            reviewed("e4", 30, None, None),
+            reviewed("f6", 120, Some(Classification::Mistake), Some("e5")),
+            reviewed("d4", 110, None, None),
+        ];
+
+        assert_eq!(
+            annotated_movetext(&moves, 1, false),
+            "1. e4 { [%eval 0.30] } 1... f6 $2 { [%eval 1.20] } (1... e5) 2. d4 { [%eval 1.10] }"
+        );
+    }
+}
@@ -4,31 +4,51 @@ use std::sync::Arc;
 
 use axum::{
     Json, Router,
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
     extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
-    response::sse::{Event, Sse},
+    http::{HeaderMap, StatusCode, header},
+    response::sse::{Event, KeepAlive, Sse},
+    response::{IntoResponse, Response},
     routing::{get, post},
 };
-use futures::Stream;
-use tokio::sync::RwLock;
-use tokio::time::{self, Duration};
+use futures::{SinkExt, Stream, StreamExt};
+use shakmaty::uci::UciMove;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{RwLock, broadcast};
 use uuid::Uuid;
 
+use crate::analysis::{self, Analysis};
 use crate::api::{
-    EngineInfo, EnginesResponse, MatchCreateRequest, MatchCreateResponse, MatchStatusResponse,
+    AnalysisEvent, AnalyzeRequest, ClockEvent, ControlMessage, EngineCrashedEvent, EngineInfo, EnginesResponse, ErrorEvent, InfoEvent, MatchCreateRequest, MatchCreateResponse,
+    MatchCreatedEvent, MatchErrorEvent, MatchEvent, MatchFinishedEvent, MatchStartedEvent, MatchStatsResponse, MatchStatusResponse,
+    MoveEvent, MoveSummaryEvent, ResultEvent, ReviewRequest, ServerEvent, SuiteRequest, TournamentCreateResponse,
+    TournamentFinishedEvent, TournamentProgressEvent, TournamentRequest,
 };
-use crate::domain::{Clock, MatchState, MatchStatus, Side};
+use crate::bench::BenchLimit;
+use crate::chess960;
+use crate::domain::{MatchState, MatchStatus, MoveSnapshot, ResultReason, Side, TimeControl, Variant, unix_time_ms};
 use crate::engine::EngineSpec;
-use crate::match_runner::run_match;
+use crate::human::{HUMAN_ID, HumanMove, human_spec};
+use crate::match_runner::{MatchConfig, MatchControl, MatchHandle, initial_fen, run_match, start_position_for};
+use crate::review::{GameReview, review_game};
+use crate::suite::{self, SuiteReport};
+use crate::syzygy::Tablebases;
+use crate::tournament::{FinishedGame, TournamentConfig, run_tournament};
+use crate::transcript::{MatchTranscripts, TranscriptLine};
 
-const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const SERVER_EVENT_CAPACITY: usize = 1024;
 
 #[derive(Clone)]
 pub struct AppState {
     engines: Arc<Vec<EngineInfo>>,
     engine_specs: Arc<HashMap<String, EngineSpec>>,
     matches: Arc<RwLock<HashMap<String, MatchState>>>,
+    handles: Arc<RwLock<HashMap<String, MatchHandle>>>,
+    reviews: Arc<RwLock<HashMap<String, GameReview>>>,
+    transcripts: Arc<RwLock<HashMap<String, MatchTranscripts>>>,
+    tablebases: Option<Arc<Tablebases>>,
+    server_events: broadcast::Sender<ServerEvent>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -36,32 +56,58 @@ struct ErrorResponse {
     error: String,
 }
 
-pub fn build_router(engines: Vec<EngineSpec>) -> Router {
-    let engine_info: Vec<EngineInfo> = engines
-        .iter()
-        .map(|engine| EngineInfo {
-            id: engine.id.clone(),
-            name: engine.name.clone(),
-            author: engine.author.clone(),
-        })
-        .collect();
+pub fn build_router(engines: Vec<EngineSpec>, tablebases: Option<Tablebases>) -> Router {
+    let mut state = AppState::new(engines);
+    state.tablebases = tablebases.map(Arc::new);
+    router(state)
+}
 
-    let engine_specs = engines
-        .into_iter()
-        .map(|engine| (engine.id.clone(), engine))
-        .collect();
+impl AppState {
+    fn new(engines: Vec<EngineSpec>) -> Self {
+        let engine_info: Vec<EngineInfo> = engines
+            .iter()
+            .map(|engine| EngineInfo {
+                id: engine.id.clone(),
+                name: engine.name.clone(),
+                author: engine.author.clone(),
+            })
+            .collect();
 
-    let state = AppState {
-        engines: Arc::new(engine_info),
-        engine_specs: Arc::new(engine_specs),
-        matches: Arc::new(RwLock::new(HashMap::new())),
-    };
+        let engine_specs = engines
+            .into_iter()
+            .map(|engine| (engine.id.clone(), engine))
+            .collect();
+
+        AppState {
+            engines: Arc::new(engine_info),
+            engine_specs: Arc::new(engine_specs),
+            matches: Arc::new(RwLock::new(HashMap::new())),
+            handles: Arc::new(RwLock::new(HashMap::new())),
+            reviews: Arc::new(RwLock::new(HashMap::new())),
+            transcripts: Arc::new(RwLock::new(HashMap::new())),
+            tablebases: None,
+            server_events: broadcast::channel(SERVER_EVENT_CAPACITY).0,
+        }
+    }
+}
 
+fn router(state: AppState) -> Router {
     Router::new()
         .route("/api/engines", get(get_engines))
+        .route("/api/events", get(stream_server_events))
         .route("/api/match", post(create_match))
         .route("/api/match/:id", get(get_match))
         .route("/api/match/:id/stream", get(stream_match))
+        .route("/api/match/:id/pause", post(pause_match))
+        .route("/api/match/:id/resume", post(resume_match))
+        .route("/api/match/:id/abort", post(abort_match))
+        .route("/api/match/:id/ws", get(match_socket))
+        .route("/api/match/:id/stats", get(get_match_stats))
+        .route("/api/match/:id/review", get(get_review).post(review_match))
+        .route("/api/match/:id/log/:side", get(get_match_log))
+        .route("/api/suite", post(run_suite))
+        .route("/api/tournament", post(start_tournament))
+        .route("/api/analyze", post(analyze))
         .with_state(state)
 }
 
@@ -86,8 +132,9 @@ async fn create_match(
         ));
     }
 
-    let white_engine = match state.engine_specs.get(&payload.white_engine_id) {
-        Some(engine) => engine.clone(),
+    let find_engine = |id: &str| if id == HUMAN_ID { Some(human_spec()) } else { state.engine_specs.get(id).cloned() };
+    let white_engine = match find_engine(&payload.white_engine_id) {
+        Some(engine) => engine,
         None => {
             return Err((
                 StatusCode::BAD_REQUEST,
@@ -98,8 +145,8 @@ async fn create_match(
         }
     };
 
-    let black_engine = match state.engine_specs.get(&payload.black_engine_id) {
-        Some(engine) => engine.clone(),
+    let black_engine = match find_engine(&payload.black_engine_id) {
+        Some(engine) => engine,
         None => {
             return Err((
                 StatusCode::BAD_REQUEST,
@@ -110,6 +157,21 @@ async fn create_match(
         }
     };
 
+    let reviewer = match &payload.review {
+        Some(review) => match state.engine_specs.get(&review.engine_id) {
+            Some(engine) => Some((engine.clone(), review.depth)),
+            None => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: "unknown review engine id".to_string(),
+                    }),
+                ));
+            }
+        },
+        None => None,
+    };
+
     if payload.white_engine_id == payload.black_engine_id {
         return Err((
             StatusCode::BAD_REQUEST,
@@ -119,45 +181,233 @@ async fn create_match(
         ));
     }
 
+    if payload.chess960_index.is_some() && payload.variant != Variant::Chess960 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "chess960_index needs variant chess960".to_string(),
+            }),
+        ));
+    }
+
+    payload.adjudication.validate().map_err(|error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })))?;
+
     let match_id = Uuid::new_v4().to_string();
-    let state_entry = MatchState {
-        match_id: match_id.clone(),
-        status: MatchStatus::Running,
-        current_fen: START_FEN.to_string(),
-        pgn: String::new(),
-        clocks: Clock {
-            white_ms: payload.time_control.initial_ms,
-            black_ms: payload.time_control.initial_ms,
-        },
-        result: None,
-        side_to_move: Side::White,
-        ply: 0,
-        start_fen: START_FEN.to_string(),
-        last_move: None,
+    let time_control = TimeControl {
+        initial_ms: payload.time_control.initial_ms,
+        increment_ms: payload.time_control.increment_ms,
+    };
+    let start_fen = match (payload.variant, payload.chess960_index) {
+        (Variant::Chess960, index) => {
+            let index = index.unwrap_or_else(|| chess960::pair_index(chess960::random_seed(), 0));
+            chess960::start_fen(index).map_err(|error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })))?
+        }
+        (variant, _) => initial_fen(variant),
     };
+    let human = if payload.white_engine_id == HUMAN_ID {
+        Some(Side::White)
+    } else if payload.black_engine_id == HUMAN_ID {
+        Some(Side::Black)
+    } else {
+        None
+    };
+    let mut state_entry = MatchState::new(match_id.clone(), start_fen.clone(), payload.variant, time_control);
+    state_entry.human = human;
 
     let mut matches = state.matches.write().await;
     matches.insert(match_id.clone(), state_entry);
+    drop(matches);
+
+    let (handle, inbox) = MatchHandle::new();
+    let events = handle.events.clone();
+    let match_events = events.subscribe();
+    state.handles.write().await.insert(match_id.clone(), handle);
+
+    let _ = state.server_events.send(ServerEvent::Created(MatchCreatedEvent {
+        match_id: match_id.clone(),
+        white_engine_id: payload.white_engine_id.clone(),
+        black_engine_id: payload.black_engine_id.clone(),
+        variant: payload.variant,
+        initial_ms: payload.time_control.initial_ms,
+    }));
+    tokio::spawn(forward_server_events(
+        match_id.clone(),
+        [payload.white_engine_id.clone(), payload.black_engine_id.clone()],
+        state.matches.clone(),
+        match_events,
+        state.server_events.clone(),
+    ));
 
-    let matches = state.matches.clone();
-    let white_clone = white_engine.clone();
-    let black_clone = black_engine.clone();
-    let match_id_clone = match_id.clone();
-    let initial_ms = payload.time_control.initial_ms;
+    let transcripts = MatchTranscripts::default();
+    state.transcripts.write().await.insert(match_id.clone(), transcripts.clone());
+
+    let config = MatchConfig {
+        white: white_engine,
+        black: black_engine,
+        time_control,
+        variant: payload.variant,
+        start_fen: Some(start_fen),
+        transcripts,
+        stall_timeout_ms: payload.stall_timeout_ms,
+        timemargin_ms: payload.timemargin_ms,
+        adjudication: payload.adjudication,
+        tablebases: state.tablebases.clone(),
+        human,
+    };
+    let task_state = state.clone();
+    let task_match_id = match_id.clone();
     tokio::spawn(async move {
-        run_match(
-            match_id_clone,
-            white_clone,
-            black_clone,
-            initial_ms,
-            matches,
-        )
-        .await;
+        run_match(task_match_id.clone(), config, task_state.matches.clone(), inbox, events).await;
+        if let Some((engine, depth)) = reviewer {
+            let _ = run_review(&task_state, &task_match_id, &engine, depth).await;
+        }
     });
 
     Ok(Json(MatchCreateResponse { match_id }))
 }
 
+async fn run_suite(
+    State(state): State<AppState>,
+    Json(payload): Json<SuiteRequest>,
+) -> Result<Json<SuiteReport>, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+
+    let Some(engine) = state.engine_specs.get(&payload.engine_id) else {
+        return Err(bad_request("unknown engine id".to_string()));
+    };
+
+    let limit =
+        request_limit(payload.depth, payload.movetime_ms, suite::DEFAULT_MOVETIME_MS).map_err(bad_request)?;
+    let positions = suite::parse_epd(&payload.epd).map_err(|err| bad_request(format!("invalid epd: {err}")))?;
+
+    match suite::run_suite(engine, &positions, limit, |_| {}).await {
+        Ok(report) => Ok(Json(report)),
+        Err(error) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error }))),
+    }
+}
+
+/// Starts a tournament in the background. Its progress is published on `/api/events`.
+async fn start_tournament(
+    State(state): State<AppState>,
+    Json(payload): Json<TournamentRequest>,
+) -> Result<Json<TournamentCreateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+
+    let config = TournamentConfig::load(&payload.config).map_err(bad_request)?;
+    let engines = config
+        .players
+        .iter()
+        .map(|id| state.engine_specs.get(id).cloned().ok_or_else(|| bad_request(format!("unknown engine id: {id}"))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let tournament_id = Uuid::new_v4().to_string();
+    let task_id = tournament_id.clone();
+    let server_events = state.server_events.clone();
+    tokio::spawn(async move {
+        let on_game = |game: usize, games_total: usize, finished: &FinishedGame| {
+            let _ = server_events.send(ServerEvent::TournamentProgress(TournamentProgressEvent {
+                tournament_id: task_id.clone(),
+                event: config.event.clone(),
+                game,
+                games_total,
+                white: finished.white.clone(),
+                black: finished.black.clone(),
+                result: finished.result.clone(),
+                reason: finished.reason,
+            }));
+        };
+        let outcome = run_tournament(&config, &engines, on_game).await;
+        let _ = server_events.send(ServerEvent::TournamentFinished(TournamentFinishedEvent {
+            tournament_id: task_id,
+            event: config.event.clone(),
+            games: outcome.as_ref().map_or(0, |state| state.games.len()),
+            error: outcome.err(),
+        }));
+    });
+
+    Ok(Json(TournamentCreateResponse { tournament_id }))
+}
+
+/// Analyses one position. Clients that accept `text/event-stream` get every `info` line
+/// as it arrives followed by a `result` event; everyone else gets the result as JSON.
+async fn analyze(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<AnalyzeRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+
+    let Some(engine) = state.engine_specs.get(&payload.engine_id) else {
+        return Err(bad_request("unknown engine id".to_string()));
+    };
+    if payload.multipv == 0 {
+        return Err(bad_request("multipv must be at least 1".to_string()));
+    }
+    let limit =
+        request_limit(payload.depth, payload.movetime_ms, analysis::DEFAULT_MOVETIME_MS).map_err(bad_request)?;
+    let analysis = Analysis::new(
+        engine.clone(),
+        payload.fen.as_deref(),
+        &payload.moves,
+        limit,
+        payload.multipv,
+    )
+    .map_err(bad_request)?;
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let side = analysis.side_to_move();
+        let result = analysis::run_analysis(&analysis, |info| {
+            let _ = tx.send(AnalysisEvent::Info(InfoEvent::new(side, info)));
+        })
+        .await;
+        let _ = tx.send(match result {
+            Ok(result) => AnalysisEvent::Result(result),
+            Err(message) => AnalysisEvent::Error(ErrorEvent { message }),
+        });
+    });
+
+    let wants_stream = headers
+        .get("accept")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+    if wants_stream {
+        let stream = async_stream::stream! {
+            while let Some(event) = rx.recv().await {
+                yield Ok::<_, Infallible>(Event::default().event(event.name()).data(event.data_json()));
+            }
+        };
+        return Ok(Sse::new(stream).into_response());
+    }
+
+    while let Some(event) = rx.recv().await {
+        match event {
+            AnalysisEvent::Info(_) => {}
+            AnalysisEvent::Result(result) => return Ok(Json(result).into_response()),
+            AnalysisEvent::Error(ErrorEvent { message }) => {
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: message })));
+            }
+        }
+    }
+    Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "analysis ended without a result".to_string(),
+        }),
+    ))
+}
+
+/// A fixed search limit from optional `depth` and `movetime_ms` request fields.
+fn request_limit(depth: Option<u32>, movetime_ms: Option<u64>, default_ms: u64) -> Result<BenchLimit, String> {
+    match (depth, movetime_ms) {
+        (Some(_), Some(_)) => Err("give either depth or movetime_ms, not both".to_string()),
+        (Some(0), None) | (None, Some(0)) => Err("limit must be greater than zero".to_string()),
+        (Some(depth), None) => Ok(BenchLimit::Depth(depth)),
+        (None, Some(ms)) => Ok(BenchLimit::Movetime(ms)),
+        (None, None) => Ok(BenchLimit::Movetime(default_ms)),
+    }
+}
+
 async fn get_match(
     State(state): State<AppState>,
     Path(match_id): Path<String>,
@@ -172,101 +422,591 @@ async fn get_match(
         ));
     };
 
-    let response = MatchStatusResponse {
-        match_id: entry.match_id.clone(),
-        status: entry.status,
-        current_fen: entry.current_fen.clone(),
-        pgn: entry.pgn.clone(),
-        clocks: entry.clocks.clone(),
-        result: entry.result.clone(),
+    Ok(Json(status_response(entry)))
+}
+
+async fn get_match_stats(
+    State(state): State<AppState>,
+    Path(match_id): Path<String>,
+) -> Result<Json<MatchStatsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let matches = state.matches.read().await;
+    let Some(entry) = matches.get(&match_id) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "match not found".to_string(),
+            }),
+        ));
     };
 
-    Ok(Json(response))
+    Ok(Json(MatchStatsResponse::new(entry)))
 }
 
-async fn stream_match(
+/// One engine's transcript as text. Clients that accept `text/event-stream` get the lines
+/// so far followed by new ones as they happen, until the engine exits.
+async fn get_match_log(
+    State(state): State<AppState>,
+    Path((match_id, side)): Path<(String, Side)>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let transcript = match state.transcripts.read().await.get(&match_id) {
+        Some(transcripts) => match side {
+            Side::White => transcripts.white.clone(),
+            Side::Black => transcripts.black.clone(),
+        },
+        None => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "match not found".to_string(),
+                }),
+            ));
+        }
+    };
+
+    let wants_stream = headers
+        .get("accept")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+    if !wants_stream {
+        return Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], transcript.to_text()).into_response());
+    }
+
+    let (backlog, live) = transcript.subscribe();
+    let stream = async_stream::stream! {
+        for line in backlog {
+            yield Ok::<_, Infallible>(transcript_event(&line));
+        }
+        let Some(mut live) = live else {
+            return;
+        };
+        loop {
+            match live.recv().await {
+                Ok(line) => yield Ok(transcript_event(&line)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()).into_response())
+}
+
+fn transcript_event(line: &TranscriptLine) -> Event {
+    Event::default().event("line").data(serde_json::to_string(line).unwrap_or_default())
+}
+
+async fn get_review(
     State(state): State<AppState>,
     Path(match_id): Path<String>,
-) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<ErrorResponse>)> {
-    {
+) -> Result<Json<GameReview>, (StatusCode, Json<ErrorResponse>)> {
+    match state.reviews.read().await.get(&match_id) {
+        Some(review) => Ok(Json(review.clone())),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "no review for match".to_string(),
+            }),
+        )),
+    }
+}
+
+async fn review_match(
+    State(state): State<AppState>,
+    Path(match_id): Path<String>,
+    Json(payload): Json<ReviewRequest>,
+) -> Result<Json<GameReview>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(engine) = state.engine_specs.get(&payload.engine_id).cloned() else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "unknown engine id".to_string(),
+            }),
+        ));
+    };
+    if payload.depth == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "depth must be greater than zero".to_string(),
+            }),
+        ));
+    }
+
+    run_review(&state, &match_id, &engine, payload.depth).await.map(Json)
+}
+
+/// Reviews a finished match and stores the result for `GET /api/match/:id/review`.
+async fn run_review(
+    state: &AppState,
+    match_id: &str,
+    engine: &EngineSpec,
+    depth: u32,
+) -> Result<GameReview, (StatusCode, Json<ErrorResponse>)> {
+    let (start_fen, variant, moves) = {
         let matches = state.matches.read().await;
-        if !matches.contains_key(&match_id) {
+        let Some(entry) = matches.get(match_id) else {
             return Err((
                 StatusCode::NOT_FOUND,
                 Json(ErrorResponse {
                     error: "match not found".to_string(),
                 }),
             ));
+        };
+        if entry.status != MatchStatus::Finished {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    error: format!("match is {}", status_name(entry.status)),
+                }),
+            ));
         }
+        (entry.start_fen.clone(), entry.variant, entry.moves.clone())
+    };
+
+    let review = review_game(engine, &start_fen, variant, &moves, depth)
+        .await
+        .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error })))?;
+    state.reviews.write().await.insert(match_id.to_string(), review.clone());
+    Ok(review)
+}
+
+async fn pause_match(
+    State(state): State<AppState>,
+    Path(match_id): Path<String>,
+) -> Result<Json<MatchStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    set_match_control(&state, &match_id, MatchControl::Pause).await.map(Json)
+}
+
+async fn resume_match(
+    State(state): State<AppState>,
+    Path(match_id): Path<String>,
+) -> Result<Json<MatchStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    set_match_control(&state, &match_id, MatchControl::Run).await.map(Json)
+}
+
+async fn abort_match(
+    State(state): State<AppState>,
+    Path(match_id): Path<String>,
+) -> Result<Json<MatchStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    set_match_control(&state, &match_id, MatchControl::Abort).await.map(Json)
+}
+
+/// Forwards a control command to the runner after checking that it makes sense for the
+/// match's current status. An abort leaves the status alone; the runner reports the
+/// aborted result itself once it has stopped the engines.
+async fn set_match_control(
+    state: &AppState,
+    match_id: &str,
+    control: MatchControl,
+) -> Result<MatchStatusResponse, (StatusCode, Json<ErrorResponse>)> {
+    let mut matches = state.matches.write().await;
+    let Some(entry) = matches.get_mut(match_id) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "match not found".to_string(),
+            }),
+        ));
+    };
+
+    // The runner reports `Paused` itself once no clock is running, so a pause asked for
+    // during an engine's search is pending until the move is played.
+    let handles = state.handles.read().await;
+    let handle = handles.get(match_id);
+    let pending_pause = handle.is_some_and(|handle| *handle.control.borrow() == MatchControl::Pause);
+    let next_status = match (control, entry.status) {
+        (MatchControl::Pause, MatchStatus::Running) if !pending_pause => MatchStatus::Running,
+        (MatchControl::Run, MatchStatus::Paused) => MatchStatus::Running,
+        (MatchControl::Run, MatchStatus::Running) if pending_pause => MatchStatus::Running,
+        (MatchControl::Abort, status @ (MatchStatus::Running | MatchStatus::Paused)) => status,
+        (MatchControl::Pause, MatchStatus::Running) => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    error: "match is already pausing".to_string(),
+                }),
+            ));
+        }
+        (_, status) => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    error: format!("match is {}", status_name(status)),
+                }),
+            ));
+        }
+    };
+
+    if let Some(handle) = handle {
+        handle.control.send_replace(control);
     }
+    entry.status = next_status;
 
-    let match_id_clone = match_id.clone();
-    let state_clone = state.clone();
+    Ok(status_response(entry))
+}
 
-    let stream = async_stream::stream! {
-        let started_payload = serde_json::json!({
-            "match_id": match_id_clone,
-            "start_fen": START_FEN,
-        });
-        let started_json = serde_json::to_string(&started_payload).unwrap_or_default();
-        yield Ok(Event::default().event("match_started").data(started_json));
+fn status_name(status: MatchStatus) -> &'static str {
+    match status {
+        MatchStatus::Running => "running",
+        MatchStatus::Paused => "paused",
+        MatchStatus::Finished => "finished",
+        MatchStatus::Error => "error",
+    }
+}
 
-        let mut ticker = time::interval(Duration::from_millis(200));
-        let mut last_emitted_ply: u32 = 0;
-        loop {
-            ticker.tick().await;
+fn status_response(entry: &MatchState) -> MatchStatusResponse {
+    MatchStatusResponse {
+        match_id: entry.match_id.clone(),
+        status: entry.status,
+        variant: entry.variant,
+        start_fen: entry.start_fen.clone(),
+        current_fen: entry.current_fen.clone(),
+        pgn: entry.pgn.clone(),
+        clocks: entry.live_clocks(unix_time_ms()),
+        running: entry.running.map(|running| running.side),
+        result: entry.result.clone(),
+        crash: entry.crash.clone(),
+        latency: entry.latency,
+        human: entry.human,
+    }
+}
 
-            let snapshot = {
-                let matches = state_clone.matches.read().await;
-                matches.get(&match_id).cloned()
-            };
+async fn stream_match(
+    State(state): State<AppState>,
+    Path(match_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<ErrorResponse>)> {
+    // Every event carries the ply it belongs to as its id, so a reconnecting
+    // `EventSource` tells us exactly which moves it already has.
+    let resume_after = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u32>().ok());
 
-            let Some(snapshot) = snapshot else {
-                break;
-            };
+    let (events, snapshot) = subscribe_match(&state, &match_id).await?;
+    let stream = match_event_stream(state, match_id, snapshot, events, resume_after)
+        .map(|(ply, event)| Ok(sse_event(&event, ply)));
 
-            let clock_payload = serde_json::json!({
-                "white_ms": snapshot.clocks.white_ms,
-                "black_ms": snapshot.clocks.black_ms,
-            });
-            let clock_json = serde_json::to_string(&clock_payload).unwrap_or_default();
-            yield Ok(Event::default().event("clock").data(clock_json));
-
-            if let Some(last_move) = snapshot.last_move.clone() {
-                if last_move.ply > last_emitted_ply {
-                    last_emitted_ply = last_move.ply;
-                    let move_payload = serde_json::json!({
-                        "ply": last_move.ply,
-                        "uci": last_move.uci,
-                        "san": last_move.san,
-                        "fen": last_move.fen,
-                        "pgn": last_move.pgn,
-                    });
-                    let move_json = serde_json::to_string(&move_payload).unwrap_or_default();
-                    yield Ok(Event::default().event("move").data(move_json));
+    Ok(Sse::new(stream))
+}
+
+async fn match_socket(
+    State(state): State<AppState>,
+    Path(match_id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let (events, snapshot) = subscribe_match(&state, &match_id).await?;
+    Ok(ws.on_upgrade(move |socket| serve_match_socket(socket, state, match_id, snapshot, events)))
+}
+
+async fn serve_match_socket(
+    socket: WebSocket,
+    state: AppState,
+    match_id: String,
+    snapshot: MatchState,
+    events: broadcast::Receiver<MatchEvent>,
+) {
+    let (mut sender, mut receiver) = socket.split();
+    let stream = match_event_stream(state.clone(), match_id.clone(), snapshot, events, None);
+    let mut stream = std::pin::pin!(stream);
+
+    loop {
+        tokio::select! {
+            next = stream.next() => {
+                let Some((_, event)) = next else {
+                    break;
+                };
+                let text = serde_json::to_string(&event).unwrap_or_default();
+                if sender.send(Message::Text(text)).await.is_err() {
+                    return;
                 }
             }
+            message = receiver.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => continue,
+                };
+                if let Err(message) = handle_control_message(&state, &match_id, &text).await {
+                    let error = MatchEvent::Error(ErrorEvent { message });
+                    let text = serde_json::to_string(&error).unwrap_or_default();
+                    if sender.send(Message::Text(text)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
 
-            if snapshot.status != MatchStatus::Running {
-                if let Some(result) = snapshot.result {
-                    let result_json = serde_json::to_string(&result).unwrap_or_default();
-                    yield Ok(Event::default().event("result").data(result_json));
+    let _ = sender.send(Message::Close(None)).await;
+}
+
+async fn handle_control_message(state: &AppState, match_id: &str, text: &str) -> Result<(), String> {
+    let message: ControlMessage =
+        serde_json::from_str(text).map_err(|err| format!("invalid control message: {err}"))?;
+
+    let control = match message {
+        ControlMessage::Abort => MatchControl::Abort,
+        ControlMessage::Pause => MatchControl::Pause,
+        ControlMessage::Resume => MatchControl::Run,
+        ControlMessage::Move { uci } => return play_human_move(state, match_id, &uci).await,
+    };
+
+    set_match_control(state, match_id, control)
+        .await
+        .map(|_| ())
+        .map_err(|(_, Json(err))| err.error)
+}
+
+/// Checks a human move against the current position and hands it to the runner, tagged
+/// with the ply it was checked at so the runner can drop it if the game has moved on.
+async fn play_human_move(state: &AppState, match_id: &str, uci: &str) -> Result<(), String> {
+    let (ply, fen, variant) = {
+        let matches = state.matches.read().await;
+        let entry = matches.get(match_id).ok_or_else(|| "match not found".to_string())?;
+        let Some(human) = entry.human else {
+            return Err(format!("cannot play {uci}: match has no human player"));
+        };
+        if entry.status != MatchStatus::Running {
+            return Err(format!("cannot play {uci}: match is {}", status_name(entry.status)));
+        }
+        if entry.side_to_move != human {
+            return Err(format!("cannot play {uci}: it is not the human player's turn"));
+        }
+        (entry.ply, entry.current_fen.clone(), entry.variant)
+    };
+
+    let pos = start_position_for(&fen, variant)?;
+    let mv = uci
+        .parse::<UciMove>()
+        .ok()
+        .and_then(|parsed| parsed.to_move(&pos).ok())
+        .ok_or_else(|| format!("illegal move {uci}"))?;
+    let uci = UciMove::from_move(&mv, variant.castling_mode()).to_string();
+
+    let moves = state.handles.read().await.get(match_id).map(|handle| handle.moves.clone());
+    let moves = moves.ok_or_else(|| "match not found".to_string())?;
+    moves.try_send(HumanMove { ply, uci }).map_err(|err| match err {
+        TrySendError::Full(_) => "a move is already pending".to_string(),
+        TrySendError::Closed(_) => "match is over".to_string(),
+    })
+}
+
+/// Subscribes to a match's events and then snapshots its state, in that order: anything
+/// published after the snapshot is guaranteed to reach the receiver, and
+/// `match_event_stream` drops moves seen twice.
+async fn subscribe_match(
+    state: &AppState,
+    match_id: &str,
+) -> Result<(broadcast::Receiver<MatchEvent>, MatchState), (StatusCode, Json<ErrorResponse>)> {
+    let events = state.handles.read().await.get(match_id).map(|handle| handle.events.subscribe());
+    let snapshot = state.matches.read().await.get(match_id).cloned();
+
+    match (events, snapshot) {
+        (Some(events), Some(snapshot)) => Ok((events, snapshot)),
+        _ => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "match not found".to_string(),
+            }),
+        )),
+    }
+}
+
+/// Replays the match from `snapshot` (skipping plies up to `resume_after`) and then
+/// follows live events until the result. Each item is paired with the ply it belongs to.
+fn match_event_stream(
+    state: AppState,
+    match_id: String,
+    snapshot: MatchState,
+    mut events: broadcast::Receiver<MatchEvent>,
+    resume_after: Option<u32>,
+) -> impl Stream<Item = (u32, MatchEvent)> {
+    async_stream::stream! {
+        let mut last_emitted_ply = resume_after.unwrap_or(0);
+        if resume_after.is_none() {
+            let started = MatchEvent::Started(MatchStartedEvent {
+                match_id: snapshot.match_id.clone(),
+                variant: snapshot.variant,
+                start_fen: snapshot.start_fen.clone(),
+            });
+            yield (0, started);
+        }
+
+        for past in moves_after(&snapshot, last_emitted_ply) {
+            last_emitted_ply = past.ply;
+            yield (last_emitted_ply, MatchEvent::Move(MoveEvent::from(past)));
+        }
+        if let Some(result) = finished_result(&snapshot) {
+            yield (last_emitted_ply, result);
+            return;
+        }
+        yield (last_emitted_ply, MatchEvent::Clock(ClockEvent::live(&snapshot)));
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => {
+                    // We fell behind the channel; recover any moves we missed from the
+                    // shared state and carry on with whatever the channel still holds.
+                    let Some(snapshot) = state.matches.read().await.get(&match_id).cloned() else {
+                        break;
+                    };
+                    for missed in moves_after(&snapshot, last_emitted_ply) {
+                        last_emitted_ply = missed.ply;
+                        yield (last_emitted_ply, MatchEvent::Move(MoveEvent::from(missed)));
+                    }
+                    if let Some(result) = finished_result(&snapshot) {
+                        yield (last_emitted_ply, result);
+                        break;
+                    }
+                    yield (last_emitted_ply, MatchEvent::Clock(ClockEvent::live(&snapshot)));
+                    continue;
                 }
+                Err(RecvError::Closed) => break,
+            };
+
+            match &event {
+                MatchEvent::Started(_) => continue,
+                MatchEvent::Move(mv) => {
+                    if mv.ply <= last_emitted_ply {
+                        continue;
+                    }
+                    last_emitted_ply = mv.ply;
+                }
+                _ => {}
+            }
+
+            let is_result = matches!(event, MatchEvent::Result(_));
+            yield (last_emitted_ply, event);
+
+            if is_result {
                 break;
             }
         }
+    }
+}
+
+async fn stream_server_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut events = state.server_events.subscribe();
+
+    let stream = async_stream::stream! {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    yield Ok(Event::default().event(event.name()).data(event.data_json()));
+                }
+                // A lobby view only needs a summary, so a slow client simply skips ahead.
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
     };
 
-    Ok(Sse::new(stream))
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Condenses one match's events into server-wide lifecycle events until the match ends.
+async fn forward_server_events(
+    match_id: String,
+    engine_ids: [String; 2],
+    matches: Arc<RwLock<HashMap<String, MatchState>>>,
+    mut events: broadcast::Receiver<MatchEvent>,
+    server_events: broadcast::Sender<ServerEvent>,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+
+        let summary = match event {
+            MatchEvent::Move(mv) => ServerEvent::Move(MoveSummaryEvent {
+                match_id: match_id.clone(),
+                ply: mv.ply,
+                san: mv.san,
+                fen: mv.fen,
+            }),
+            MatchEvent::Error(error) => ServerEvent::Error(MatchErrorEvent {
+                match_id: match_id.clone(),
+                message: error.message,
+            }),
+            MatchEvent::Result(result) => {
+                if matches!(result.reason, ResultReason::Crash | ResultReason::Stalled) {
+                    // The result is published after the state is final, so the crash
+                    // report is already there.
+                    let crash = matches.read().await.get(&match_id).and_then(|entry| entry.crash.clone());
+                    let event = match crash {
+                        Some(crash) => EngineCrashedEvent::from_crash(match_id.clone(), result.reason, crash),
+                        None => {
+                            let side = if result.result == "1-0" { Side::Black } else { Side::White };
+                            let [white_id, black_id] = &engine_ids;
+                            EngineCrashedEvent {
+                                match_id: match_id.clone(),
+                                engine_id: if side == Side::White { white_id.clone() } else { black_id.clone() },
+                                side,
+                                reason: result.reason,
+                                exit_code: None,
+                                signal: None,
+                                stderr: Vec::new(),
+                            }
+                        }
+                    };
+                    let _ = server_events.send(ServerEvent::EngineCrashed(event));
+                }
+                let _ = server_events.send(ServerEvent::Finished(MatchFinishedEvent {
+                    match_id: match_id.clone(),
+                    result: result.result,
+                    reason: result.reason,
+                }));
+                break;
+            }
+            MatchEvent::Started(_) | MatchEvent::Clock(_) | MatchEvent::Info(_) => continue,
+        };
+
+        let _ = server_events.send(summary);
+    }
+}
+
+fn sse_event(event: &MatchEvent, ply: u32) -> Event {
+    Event::default()
+        .event(event.name())
+        .id(ply.to_string())
+        .data(event.data_json())
+}
+
+fn moves_after(snapshot: &MatchState, ply: u32) -> Vec<MoveSnapshot> {
+    snapshot.moves.iter().filter(|m| m.ply > ply).cloned().collect()
+}
+
+fn finished_result(snapshot: &MatchState) -> Option<MatchEvent> {
+    if !matches!(snapshot.status, MatchStatus::Finished | MatchStatus::Error) {
+        return None;
+    }
+
+    let result = snapshot.result.clone()?;
+    Some(MatchEvent::Result(ResultEvent {
+        result: result.result,
+        reason: result.reason,
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Protocol;
+    use crate::domain::{Clock, CrashReport, Latency, MoveStats};
+    use crate::transcript::Direction;
     use axum::body::{Body, to_bytes};
     use axum::http::{Request, StatusCode as HttpStatus};
+    use std::time::Duration;
     use tower::ServiceExt;
 
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
     fn sample_engines() -> Vec<EngineSpec> {
         vec![
             EngineSpec {
@@ -276,6 +1016,8 @@ mod tests {
                 path: "/opt/stockfish".into(),
                 args: vec!["-threads".to_string(), "4".to_string()],
                 working_dir: None,
+                protocol: Protocol::Uci,
+                options: Vec::new(),
             },
             EngineSpec {
                 id: "lc0-0.30".to_string(),
@@ -284,13 +1026,81 @@ mod tests {
                 path: "/opt/lc0".into(),
                 args: Vec::new(),
                 working_dir: None,
+                protocol: Protocol::Uci,
+                options: Vec::new(),
             },
         ]
     }
 
+    /// A UCI engine in a shell script that plays a fixed Italian opening for either side,
+    /// picking its reply by the length of the `position fen ... moves ...` command.
+    #[cfg(unix)]
+    fn script_engine() -> EngineSpec {
+        let script = r#"
+            while read cmd rest; do
+                case $cmd in
+                    uci) echo "id name script"; echo uciok ;;
+                    isready) echo readyok ;;
+                    position) set -- $rest; words=$# ;;
+                    go) case $words in
+                        7) echo "bestmove e2e4" ;; 9) echo "bestmove e7e5" ;; 10) echo "bestmove g1f3" ;;
+                        11) echo "bestmove b8c6" ;; 12) echo "bestmove f1c4" ;; *) echo "bestmove g8f6" ;;
+                    esac ;;
+                    quit) exit ;;
+                esac
+            done
+        "#;
+        EngineSpec {
+            id: "script".to_string(),
+            name: "Script".to_string(),
+            author: String::new(),
+            path: "sh".into(),
+            args: vec!["-c".to_string(), script.to_string()],
+            working_dir: None,
+            protocol: Protocol::Uci,
+            options: Vec::new(),
+        }
+    }
+
+    #[cfg(unix)]
+    async fn start_match(state: &AppState, white: &str, black: &str) -> String {
+        let request_body = serde_json::json!({
+            "white_engine_id": white,
+            "black_engine_id": black,
+            "time_control": { "initial_ms": 60000 }
+        });
+        let response = router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/match")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let created: MatchCreateResponse = serde_json::from_slice(&body).unwrap();
+        created.match_id
+    }
+
+    /// Polls the match until `ready` holds, for up to five seconds.
+    #[cfg(unix)]
+    async fn wait_for_match(state: &AppState, match_id: &str, ready: impl Fn(&MatchState) -> bool) -> MatchState {
+        for _ in 0..500 {
+            if let Some(entry) = state.matches.read().await.get(match_id).filter(|entry| ready(entry)) {
+                return entry.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("match {match_id} never reached the expected state");
+    }
+
     #[tokio::test]
     async fn get_engines_returns_configured_engines() {
-        let app = build_router(sample_engines());
+        let app = build_router(sample_engines(), None);
 
         let response = app
             .oneshot(
@@ -314,7 +1124,7 @@ mod tests {
 
     #[tokio::test]
     async fn post_match_creates_match() {
-        let app = build_router(sample_engines());
+        let app = build_router(sample_engines(), None);
 
         let request_body = serde_json::json!({
             "white_engine_id": "stockfish-16",
@@ -344,7 +1154,7 @@ mod tests {
 
     #[tokio::test]
     async fn post_match_rejects_unknown_engine() {
-        let app = build_router(sample_engines());
+        let app = build_router(sample_engines(), None);
 
         let request_body = serde_json::json!({
             "white_engine_id": "unknown",
@@ -366,4 +1176,429 @@ mod tests {
 
         assert_eq!(response.status(), HttpStatus::BAD_REQUEST);
     }
+
+    #[tokio::test]
+    async fn post_match_rejects_chess960_index_for_other_variants() {
+        let app = build_router(sample_engines(), None);
+
+        let request_body = serde_json::json!({
+            "white_engine_id": "stockfish-16",
+            "black_engine_id": "lc0-0.30",
+            "time_control": { "initial_ms": 300000 },
+            "variant": "atomic",
+            "chess960_index": 518
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/match")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), HttpStatus::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn post_match_rejects_a_zero_move_limit() {
+        let app = build_router(sample_engines(), None);
+
+        let request_body = serde_json::json!({
+            "white_engine_id": "stockfish-16",
+            "black_engine_id": "lc0-0.30",
+            "time_control": { "initial_ms": 300000 },
+            "adjudication": { "max_plies": 0 }
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/match")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), HttpStatus::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn post_suite_rejects_invalid_epd() {
+        let app = build_router(sample_engines(), None);
+
+        let request_body = serde_json::json!({
+            "engine_id": "stockfish-16",
+            "epd": "8/8/8/8/8/8/8/K1k5 w - - bm Qh8;",
+            "depth": 10
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/suite")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), HttpStatus::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn pause_unknown_match_returns_not_found() {
+        let app = build_router(sample_engines(), None);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/match/missing/pause")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), HttpStatus::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn review_of_unreviewed_match_returns_not_found() {
+        let state = AppState::new(sample_engines());
+        insert_finished_match(&state, "m1", 2).await;
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/api/match/m1/review").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), HttpStatus::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn stats_return_per_ply_arrays_and_aggregates() {
+        let state = AppState::new(sample_engines());
+        insert_finished_match(&state, "m1", 4).await;
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/api/match/m1/stats").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stats["ply"], serde_json::json!([1, 2, 3, 4]));
+        assert_eq!(stats["side"], serde_json::json!(["white", "black", "white", "black"]));
+        assert_eq!(stats["eval"], serde_json::json!([10, -20, 30, -40]));
+        assert_eq!(stats["clock_ms"], serde_json::json!([950, 950, 900, 900]));
+        assert_eq!(stats["white"]["average_depth"], serde_json::json!(2.0));
+        assert_eq!(stats["black"]["total_time_ms"], serde_json::json!(100));
+    }
+
+    #[tokio::test]
+    async fn match_log_returns_the_transcript_of_one_side() {
+        let state = AppState::new(sample_engines());
+        let transcripts = MatchTranscripts::default();
+        transcripts.black.record(Direction::Sent, "uci");
+        transcripts.black.record(Direction::Stderr, "info: loading net");
+        state.transcripts.write().await.insert("m1".to_string(), transcripts);
+
+        let app = router(state);
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/api/match/m1/log/black").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains("> uci") && text.contains("! info: loading net"));
+
+        let missing = app
+            .oneshot(Request::builder().uri("/api/match/m2/log/white").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), HttpStatus::NOT_FOUND);
+    }
+
+    async fn insert_finished_match(state: &AppState, match_id: &str, plies: u32) {
+        let moves = (1..=plies)
+            .map(|ply| MoveSnapshot {
+                ply,
+                uci: format!("m{ply}"),
+                san: format!("M{ply}"),
+                fen: START_FEN.to_string(),
+                pgn: String::new(),
+                stats: MoveStats {
+                    score: Some(crate::uci::Score::Cp(10 * ply as i32)),
+                    depth: Some(ply),
+                    time_ms: 50,
+                    clock_ms: 1000 - 50 * ply.div_ceil(2) as u64,
+                    ..MoveStats::default()
+                },
+            })
+            .collect();
+
+        let entry = MatchState {
+            match_id: match_id.to_string(),
+            status: MatchStatus::Finished,
+            current_fen: START_FEN.to_string(),
+            pgn: String::new(),
+            clocks: Clock {
+                white_ms: 1000,
+                black_ms: 1000,
+            },
+            result: Some(crate::domain::MatchResult {
+                result: "1/2-1/2".to_string(),
+                reason: crate::domain::ResultReason::Draw,
+            }),
+            side_to_move: Side::White,
+            ply: plies,
+            start_fen: START_FEN.to_string(),
+            variant: Variant::Standard,
+            time_control: TimeControl {
+                initial_ms: 1000,
+                increment_ms: 0,
+            },
+            moves,
+            crash: None,
+            latency: Latency::default(),
+            running: None,
+            human: None,
+        };
+
+        state.matches.write().await.insert(match_id.to_string(), entry);
+        let (handle, _) = MatchHandle::new();
+        state.handles.write().await.insert(match_id.to_string(), handle);
+    }
+
+    #[tokio::test]
+    async fn stream_replays_full_history_with_ply_ids() {
+        let state = AppState::new(sample_engines());
+        insert_finished_match(&state, "done", 3).await;
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/match/done/stream")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body.contains("event: match_started"));
+        for ply in 1..=3 {
+            assert!(body.contains(&format!("event: move\nid: {ply}\n")));
+        }
+        assert!(body.contains("event: result"));
+    }
+
+    #[tokio::test]
+    async fn stream_resumes_after_last_event_id() {
+        let state = AppState::new(sample_engines());
+        insert_finished_match(&state, "done", 3).await;
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/match/done/stream")
+                    .header("last-event-id", "2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(!body.contains("event: match_started"));
+        assert!(!body.contains("\"ply\":2"));
+        assert!(body.contains("event: move\nid: 3\n"));
+        assert!(body.contains("event: result"));
+    }
+
+    #[tokio::test]
+    async fn crashes_are_announced_before_the_result() {
+        let tc = TimeControl { initial_ms: 1000, increment_ms: 0 };
+        let mut entry = MatchState::new("c".to_string(), START_FEN.to_string(), Variant::Standard, tc);
+        entry.crash = Some(CrashReport {
+            side: Side::Black,
+            engine_id: "lc0".to_string(),
+            exit_code: None,
+            signal: Some(11),
+            stderr: vec!["segfault".to_string()],
+        });
+        let matches = Arc::new(RwLock::new(HashMap::from([("c".to_string(), entry)])));
+        let (events, _) = broadcast::channel(8);
+        let (server_events, mut received) = broadcast::channel(8);
+        let ids = ["sf".to_string(), "lc0".to_string()];
+        let forward = tokio::spawn(forward_server_events("c".to_string(), ids, matches, events.subscribe(), server_events));
+
+        events.send(MatchEvent::Result(ResultEvent { result: "1-0".to_string(), reason: ResultReason::Crash })).unwrap();
+        forward.await.unwrap();
+
+        let ServerEvent::EngineCrashed(crash) = received.recv().await.unwrap() else {
+            panic!("expected engine_crashed");
+        };
+        assert_eq!((crash.engine_id.as_str(), crash.side, crash.signal), ("lc0", Side::Black, Some(11)));
+        assert!(matches!(received.recv().await.unwrap(), ServerEvent::Finished(_)));
+    }
+
+    #[tokio::test]
+    async fn human_moves_are_checked_before_reaching_the_runner() {
+        let state = AppState::new(sample_engines());
+        let tc = TimeControl { initial_ms: 1000, increment_ms: 0 };
+        let mut entry = MatchState::new("h".to_string(), START_FEN.to_string(), Variant::Standard, tc);
+        entry.human = Some(Side::White);
+        state.matches.write().await.insert("h".to_string(), entry);
+        let (handle, mut inbox) = MatchHandle::new();
+        state.handles.write().await.insert("h".to_string(), handle);
+
+        let play = |uci: &str| format!(r#"{{"type": "move", "uci": "{uci}"}}"#);
+        assert_eq!(handle_control_message(&state, "h", &play("e2e5")).await, Err("illegal move e2e5".to_string()));
+        assert_eq!(handle_control_message(&state, "h", &play("e2e4")).await, Ok(()));
+        let sent = inbox.moves.try_recv().expect("move for the runner");
+        assert_eq!((sent.ply, sent.uci.as_str()), (0, "e2e4"));
+
+        state.matches.write().await.get_mut("h").expect("match").human = Some(Side::Black);
+        let refused = handle_control_message(&state, "h", &play("e7e5")).await;
+        assert_eq!(refused, Err("cannot play e7e5: it is not the human player's turn".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn humans_answer_engine_moves_through_the_runner() {
+        let state = AppState::new(vec![script_engine()]);
+        let match_id = start_match(&state, "script", HUMAN_ID).await;
+        let play = |uci: &str| format!(r#"{{"type": "move", "uci": "{uci}"}}"#);
+
+        let engine_moved = wait_for_match(&state, &match_id, |entry| entry.ply == 1).await;
+        assert_eq!(engine_moved.side_to_move, Side::Black);
+        assert_eq!(handle_control_message(&state, &match_id, &play("e7e5")).await, Ok(()));
+
+        wait_for_match(&state, &match_id, |entry| entry.ply == 3).await;
+        assert_eq!(handle_control_message(&state, &match_id, &play("b8c6")).await, Ok(()));
+
+        let replied = wait_for_match(&state, &match_id, |entry| entry.ply == 5).await;
+        assert_eq!(replied.pgn, "1. e4 e5 2. Nf3 Nc6 3. Bc4");
+
+        set_match_control(&state, &match_id, MatchControl::Abort).await.expect("abort");
+        wait_for_match(&state, &match_id, |entry| entry.status == MatchStatus::Finished).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pausing_stops_the_clocks_on_a_human_turn() {
+        let state = AppState::new(vec![script_engine()]);
+        let match_id = start_match(&state, HUMAN_ID, "script").await;
+        wait_for_match(&state, &match_id, |entry| entry.running.is_some_and(|running| running.side == Side::White))
+            .await;
+
+        set_match_control(&state, &match_id, MatchControl::Pause).await.expect("pause");
+        let paused = wait_for_match(&state, &match_id, |entry| entry.status == MatchStatus::Paused).await;
+        assert_eq!(paused.running, None);
+        let frozen = paused.live_clocks(unix_time_ms());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let later = state.matches.read().await[&match_id].live_clocks(unix_time_ms());
+        assert_eq!((later.white_ms, later.black_ms), (frozen.white_ms, frozen.black_ms));
+
+        let resumed = set_match_control(&state, &match_id, MatchControl::Run).await.expect("resume");
+        assert_eq!(resumed.status, MatchStatus::Running);
+        wait_for_match(&state, &match_id, |entry| entry.running.is_some()).await;
+
+        set_match_control(&state, &match_id, MatchControl::Abort).await.expect("abort");
+        wait_for_match(&state, &match_id, |entry| entry.status == MatchStatus::Finished).await;
+    }
+
+    #[cfg(unix)]
+    async fn next_server_event(received: &mut broadcast::Receiver<ServerEvent>) -> ServerEvent {
+        tokio::time::timeout(Duration::from_secs(5), received.recv()).await.expect("server event").expect("open stream")
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn tournament_progress_is_published_on_the_server_stream() {
+        let dir = std::env::temp_dir().join(format!("chessbench-tournament-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("tournament.toml");
+        let description = r#"
+            engines = "engines.toml"
+            format = "round-robin"
+            players = ["script", "script-2"]
+            tc = "10"
+            pgn_out = "games.pgn"
+            state_file = "state.json"
+        "#;
+        std::fs::write(&config, description).unwrap();
+
+        let second = EngineSpec { id: "script-2".to_string(), ..script_engine() };
+        let state = AppState::new(vec![script_engine(), second]);
+        let mut received = state.server_events.subscribe();
+        let request_body = serde_json::json!({ "config": config });
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/tournament")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::OK);
+
+        for game in 1..=2 {
+            let ServerEvent::TournamentProgress(progress) = next_server_event(&mut received).await else {
+                panic!("expected tournament_progress");
+            };
+            assert_eq!((progress.game, progress.games_total), (game, 2));
+            // The script runs out of moves as white and loses by an illegal move.
+            assert_eq!((progress.result.as_str(), progress.reason), ("0-1", Some(ResultReason::Illegal)));
+        }
+        let ServerEvent::TournamentFinished(finished) = next_server_event(&mut received).await else {
+            panic!("expected tournament_finished");
+        };
+        assert_eq!((finished.games, finished.error), (2, None));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn post_tournament_rejects_a_missing_config() {
+        let app = build_router(sample_engines(), None);
+        let request_body = serde_json::json!({ "config": "/nonexistent/tournament.toml" });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/tournament")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), HttpStatus::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,254 @@
+use crate::domain::MoveStats;
+
+/// Win/draw/loss tally from one player's point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Score {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Score {
+    /// Records a PGN result (`1-0`, `0-1`, `1/2-1/2`) for the player who had `white`.
+    /// Unfinished games (`*`) are not counted.
+    pub fn record(&mut self, result: &str, white: bool) {
+        match (result, white) {
+            ("1-0", true) | ("0-1", false) => self.wins += 1,
+            ("1-0", false) | ("0-1", true) => self.losses += 1,
+            ("1/2-1/2", _) => self.draws += 1,
+            _ => {}
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn points(&self) -> f64 {
+        f64::from(self.wins) + f64::from(self.draws) / 2.0
+    }
+
+    /// Fraction of the available points scored, or `None` before any game.
+    pub fn ratio(&self) -> Option<f64> {
+        let games = self.games();
+        (games > 0).then(|| self.points() / f64::from(games))
+    }
+
+    /// Logistic Elo difference with its 95% confidence margin. `None` until the score is
+    /// strictly between 0% and 100%, where the estimate is unbounded.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let games = f64::from(self.games());
+        let ratio = self.ratio()?;
+        if ratio <= 0.0 || ratio >= 1.0 {
+            return None;
+        }
+
+        let win = f64::from(self.wins) / games;
+        let draw = f64::from(self.draws) / games;
+        let loss = f64::from(self.losses) / games;
+        let variance = win * (1.0 - ratio).powi(2) + draw * (0.5 - ratio).powi(2) + loss * ratio.powi(2);
+        let margin = 1.959_964 * (variance / games).sqrt();
+
+        let low = elo_from_ratio(ratio - margin);
+        let high = elo_from_ratio(ratio + margin);
+        Some((elo_from_ratio(ratio), (high - low) / 2.0))
+    }
+}
+
+/// Expected score for a player `elo` points stronger than its opponent.
+pub fn ratio_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtDecision {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+/// Sequential probability ratio test of `elo0` (H0) against `elo1` (H1), using the
+/// normal approximation to the game score that fishtest uses for logistic Elo.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    #[serde(default = "default_sprt_error")]
+    pub alpha: f64,
+    #[serde(default = "default_sprt_error")]
+    pub beta: f64,
+}
+
+fn default_sprt_error() -> f64 {
+    0.05
+}
+
+impl Sprt {
+    /// Log-likelihood ratio of the score so far; zero until it carries information.
+    pub fn llr(&self, score: &Score) -> f64 {
+        let games = f64::from(score.games());
+        let Some(ratio) = score.ratio() else {
+            return 0.0;
+        };
+
+        let win = f64::from(score.wins) / games;
+        let draw = f64::from(score.draws) / games;
+        let loss = f64::from(score.losses) / games;
+        let variance = win * (1.0 - ratio).powi(2) + draw * (0.5 - ratio).powi(2) + loss * ratio.powi(2);
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let s0 = ratio_from_elo(self.elo0);
+        let s1 = ratio_from_elo(self.elo1);
+        (s1 - s0) * (2.0 * ratio - s0 - s1) / (2.0 * variance / games)
+    }
+
+    /// Lower and upper LLR bounds for the configured error rates.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    pub fn decision(&self, score: &Score) -> SprtDecision {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtDecision::AcceptH1
+        } else if llr <= lower {
+            SprtDecision::AcceptH0
+        } else {
+            SprtDecision::Continue
+        }
+    }
+}
+
+pub fn elo_from_ratio(ratio: f64) -> f64 {
+    let ratio = ratio.clamp(1e-6, 1.0 - 1e-6);
+    400.0 * (ratio / (1.0 - ratio)).log10()
+}
+
+/// A move is played in time trouble when the mover's clock afterwards is below this share
+/// of the initial time.
+const TIME_TROUBLE_SHARE: u64 = 10;
+
+/// How one engine spent its time and searched over a game.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct EngineUsage {
+    pub moves: u32,
+    pub average_depth: Option<f64>,
+    pub average_nps: Option<u64>,
+    pub total_time_ms: u64,
+    pub time_trouble_moves: u32,
+    /// Moves that went past the clock but were let through by the time margin.
+    pub overruns: u32,
+    /// Ping round trip measured before the game, in microseconds.
+    pub latency_us: u64,
+}
+
+impl EngineUsage {
+    /// Aggregates one side's moves. NPS averages the engines' own `nps` reports.
+    pub fn from_moves<'a>(moves: impl IntoIterator<Item = &'a MoveStats>, initial_ms: u64) -> Self {
+        let trouble_ms = initial_ms * TIME_TROUBLE_SHARE / 100;
+        let mut usage = EngineUsage::default();
+        let (mut depths, mut depth_sum) = (0u32, 0u64);
+        let (mut nps_reports, mut nps_sum) = (0u64, 0u64);
+
+        for stats in moves {
+            usage.moves += 1;
+            usage.total_time_ms += stats.time_ms;
+            if stats.clock_ms < trouble_ms {
+                usage.time_trouble_moves += 1;
+            }
+            if stats.overrun_ms > 0 {
+                usage.overruns += 1;
+            }
+            if let Some(depth) = stats.depth {
+                depths += 1;
+                depth_sum += u64::from(depth);
+            }
+            if let Some(nps) = stats.nps {
+                nps_reports += 1;
+                nps_sum += nps;
+            }
+        }
+
+        usage.average_depth = (depths > 0).then(|| depth_sum as f64 / f64::from(depths));
+        usage.average_nps = (nps_reports > 0).then(|| nps_sum / nps_reports);
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_results_from_both_colours() {
+        let mut score = Score::default();
+        score.record("1-0", true);
+        score.record("1-0", false);
+        score.record("0-1", false);
+        score.record("1/2-1/2", true);
+        score.record("*", true);
+
+        assert_eq!(score, Score { wins: 2, losses: 1, draws: 1 });
+        assert_eq!(score.points(), 2.5);
+    }
+
+    #[test]
+    fn estimates_elo_difference() {
+        let even = Score { wins: 10, losses: 10, draws: 20 };
+        let (elo, margin) = even.elo().expect("elo");
+        assert!(elo.abs() < 1e-9);
+        assert!(margin > 0.0);
+
+        let ahead = Score { wins: 30, losses: 10, draws: 10 };
+        let (elo, _) = ahead.elo().expect("elo");
+        assert!((elo - 147.19).abs() < 0.1);
+
+        assert_eq!(Score { wins: 3, losses: 0, draws: 0 }.elo(), None);
+    }
+
+    #[test]
+    fn sprt_accepts_h1_for_a_clearly_stronger_engine() {
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        let (lower, upper) = sprt.bounds();
+        assert!((upper - 2.944).abs() < 0.001);
+        assert!((lower + 2.944).abs() < 0.001);
+
+        let strong = Score { wins: 600, losses: 400, draws: 1000 };
+        assert_eq!(sprt.decision(&strong), SprtDecision::AcceptH1);
+
+        let weak = Score { wins: 400, losses: 600, draws: 1000 };
+        assert_eq!(sprt.decision(&weak), SprtDecision::AcceptH0);
+
+        let early = Score { wins: 3, losses: 2, draws: 5 };
+        assert_eq!(sprt.decision(&early), SprtDecision::Continue);
+    }
+
+    #[test]
+    fn aggregates_engine_usage() {
+        let stats = |depth, nps, time_ms, clock_ms| MoveStats {
+            depth: Some(depth),
+            nps,
+            time_ms,
+            clock_ms,
+            ..MoveStats::default()
+        };
+        let mut moves = [stats(10, Some(1_000), 400, 9_600), stats(14, None, 500, 900), stats(12, Some(3_000), 100, 800)];
+        moves[1].overrun_ms = 20;
+
+        let usage = EngineUsage::from_moves(&moves, 10_000);
+        assert_eq!(usage.moves, 3);
+        assert_eq!(usage.average_depth, Some(12.0));
+        assert_eq!(usage.average_nps, Some(2_000));
+        assert_eq!(usage.total_time_ms, 1_000);
+        assert_eq!(usage.time_trouble_moves, 2);
+        assert_eq!(usage.overruns, 1);
+    }
+}
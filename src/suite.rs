@@ -0,0 +1,308 @@
+use serde::Serialize;
+use shakmaty::san::San;
+use shakmaty::uci::UciMove;
+use shakmaty::{Chess, Move};
+
+use crate::bench::BenchLimit;
+use crate::engine::EngineSpec;
+use crate::match_runner::start_position;
+use crate::protocol::spawn_spec;
+use crate::transcript::Transcript;
+
+/// Full credit for a move in an STS-style point list.
+const MAX_POINTS: u32 = 10;
+pub const DEFAULT_MOVETIME_MS: u64 = 1000;
+
+/// One test position from an EPD file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpdPosition {
+    pub id: Option<String>,
+    pub fen: String,
+    /// `bm`: any of these moves solves the position.
+    pub best: Vec<String>,
+    /// `am`: none of these moves may be played.
+    pub avoid: Vec<String>,
+    /// STS partial credit from `c0`/`c1`, as SAN and points out of 10.
+    pub points: Vec<(String, u32)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionReport {
+    pub id: Option<String>,
+    pub fen: String,
+    pub bestmove: String,
+    pub san: Option<String>,
+    pub solved: bool,
+    pub points: u32,
+    pub max_points: u32,
+    pub depth: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SuiteReport {
+    pub engine_id: String,
+    pub solved: usize,
+    pub total: usize,
+    pub points: u32,
+    pub max_points: u32,
+    pub positions: Vec<PositionReport>,
+}
+
+/// Parses EPD text: four position fields followed by `;`-terminated operations. Every
+/// position needs a `bm`, `am` or point list, and every listed move must be legal.
+pub fn parse_epd(text: &str) -> Result<Vec<EpdPosition>, String> {
+    let mut positions = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let position = parse_epd_line(line).map_err(|err| format!("line {}: {err}", idx + 1))?;
+        positions.push(position);
+    }
+
+    if positions.is_empty() {
+        return Err("no positions found".to_string());
+    }
+    Ok(positions)
+}
+
+fn parse_epd_line(line: &str) -> Result<EpdPosition, String> {
+    let mut fields = line.splitn(5, char::is_whitespace);
+    let board: Vec<&str> = fields.by_ref().take(4).collect();
+    if board.len() < 4 {
+        return Err(format!("expected four position fields: {line}"));
+    }
+    let fen = format!("{} 0 1", board.join(" "));
+    let pos = start_position(&fen)?;
+
+    let mut position = EpdPosition {
+        id: None,
+        fen,
+        best: Vec::new(),
+        avoid: Vec::new(),
+        points: Vec::new(),
+    };
+    let mut c0 = Vec::new();
+    let mut c1 = Vec::new();
+
+    for operation in split_operations(fields.next().unwrap_or("")) {
+        let Some((opcode, operands)) = operation.split_first() else {
+            continue;
+        };
+        match opcode.as_str() {
+            "bm" => position.best = operands.to_vec(),
+            "am" => position.avoid = operands.to_vec(),
+            "id" => position.id = operands.first().cloned(),
+            "c0" => c0 = operands.to_vec(),
+            "c1" => c1 = operands.to_vec(),
+            _ => {}
+        }
+    }
+    position.points = point_list(&c0, &c1);
+
+    if position.best.is_empty() && position.avoid.is_empty() && position.points.is_empty() {
+        return Err("position has no bm, am or c0 point list".to_string());
+    }
+    let listed = position.best.iter().chain(&position.avoid).chain(position.points.iter().map(|(san, _)| san));
+    for san in listed {
+        san_move(&pos, san).ok_or_else(|| format!("illegal move {san} in {}", position.fen))?;
+    }
+
+    Ok(position)
+}
+
+/// Splits EPD operations on `;`, then each into opcode and operands. Quoted operands are
+/// kept whole and may contain `;`.
+fn split_operations(text: &str) -> Vec<Vec<String>> {
+    let mut operations = Vec::new();
+    let mut current = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                if !token.is_empty() {
+                    current.push(std::mem::take(&mut token));
+                }
+                operations.push(std::mem::take(&mut current));
+            }
+            ch if ch.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    current.push(std::mem::take(&mut token));
+                }
+            }
+            ch => token.push(ch),
+        }
+    }
+    if !token.is_empty() {
+        current.push(token);
+    }
+    if !current.is_empty() {
+        operations.push(current);
+    }
+    operations
+}
+
+/// STS suites give partial credit either as `c0 "Nd5=10, Nh5=5"` or as moves in `c0`
+/// with the matching points in `c1`.
+fn point_list(c0: &[String], c1: &[String]) -> Vec<(String, u32)> {
+    let c0 = c0.join(" ");
+    if c0.contains('=') {
+        return c0
+            .split(',')
+            .filter_map(|entry| {
+                let (san, points) = entry.trim().split_once('=')?;
+                Some((san.trim().to_string(), points.trim().parse().ok()?))
+            })
+            .collect();
+    }
+
+    let moves: Vec<&str> = c0.split_whitespace().collect();
+    let points: Vec<u32> = c1.join(" ").split_whitespace().filter_map(|value| value.parse().ok()).collect();
+    if moves.is_empty() || moves.len() != points.len() {
+        return Vec::new();
+    }
+    moves.into_iter().map(str::to_string).zip(points).collect()
+}
+
+fn san_move(pos: &Chess, san: &str) -> Option<Move> {
+    let san: San = san.trim_end_matches(['!', '?']).parse().ok()?;
+    san.to_move(pos).ok()
+}
+
+/// Scores the engine's move: `(solved, points, max_points)`. Positions with a point list
+/// are worth 10 points, all others one point for a solve.
+pub fn score_move(position: &EpdPosition, pos: &Chess, played: &Move) -> (bool, u32, u32) {
+    let matches = |san: &String| san_move(pos, san).as_ref() == Some(played);
+
+    let solved = (position.best.is_empty() || position.best.iter().any(matches))
+        && !position.avoid.iter().any(matches);
+
+    if position.points.is_empty() {
+        return (solved, u32::from(solved), 1);
+    }
+    let points = position
+        .points
+        .iter()
+        .find(|(san, _)| matches(san))
+        .map_or(0, |(_, points)| *points);
+    (solved, points.min(MAX_POINTS), MAX_POINTS)
+}
+
+/// Searches every position with one engine process and scores the results. `on_position`
+/// is called as each position finishes.
+pub async fn run_suite(
+    engine: &EngineSpec,
+    positions: &[EpdPosition],
+    limit: BenchLimit,
+    mut on_position: impl FnMut(&PositionReport),
+) -> Result<SuiteReport, String> {
+    let failed = |err| format!("{}: {err}", engine.id);
+
+    let mut process = spawn_spec(engine, Transcript::default()).await.map_err(failed)?;
+    process.handshake().await.map_err(failed)?;
+    process.configure(&engine.options).await.map_err(failed)?;
+    process.is_ready().await.map_err(failed)?;
+
+    let mut report = SuiteReport {
+        engine_id: engine.id.clone(),
+        solved: 0,
+        total: positions.len(),
+        points: 0,
+        max_points: 0,
+        positions: Vec::new(),
+    };
+
+    for position in positions {
+        let pos = start_position(&position.fen)?;
+        process.new_game(None).await.map_err(failed)?;
+
+        let mut depth = None;
+        let bestmove = process
+            .search(&position.fen, &[], &limit.search_limits(), limit.timeout_ms(), &mut |info| {
+                depth = info.depth.or(depth);
+            })
+            .await
+            .map_err(failed)?;
+
+        let played = bestmove
+            .parse::<UciMove>()
+            .ok()
+            .and_then(|uci| uci.to_move(&pos).ok());
+        let (solved, points, max_points) = match &played {
+            Some(mv) => score_move(position, &pos, mv),
+            None => (false, 0, if position.points.is_empty() { 1 } else { MAX_POINTS }),
+        };
+
+        let entry = PositionReport {
+            id: position.id.clone(),
+            fen: position.fen.clone(),
+            san: played.map(|mv| San::from_move(&pos, &mv).to_string()),
+            bestmove,
+            solved,
+            points,
+            max_points,
+            depth,
+        };
+        on_position(&entry);
+
+        report.solved += usize::from(solved);
+        report.points += points;
+        report.max_points += max_points;
+        report.positions.push(entry);
+    }
+
+    process.quit().await;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAC_001: &str = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#;
+    const STS_001: &str = r#"1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - bm f5; id "STS(v1.0) Undermine.001"; c0 "f5=10, Be5+=2, Bf2=3, Bg4=2";"#;
+
+    #[test]
+    fn parses_bm_am_id_and_point_lists() {
+        let positions = parse_epd(&format!("{WAC_001}\n# comment\n{STS_001}\n")).expect("parse epd");
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].id.as_deref(), Some("WAC.001"));
+        assert_eq!(positions[0].best, vec!["Qg6"]);
+        assert_eq!(positions[0].fen, "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        assert_eq!(positions[1].points[1], ("Be5+".to_string(), 2));
+
+        let split = parse_epd(r#"1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - c0 "f5 Bf2"; c1 "10 3";"#)
+            .expect("parse epd");
+        assert_eq!(split[0].points, vec![("f5".to_string(), 10), ("Bf2".to_string(), 3)]);
+
+        assert!(parse_epd("8/8/8/8/8/8/8/K1k5 w - - id \"none\";").is_err());
+        assert!(parse_epd("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qh8;").is_err());
+    }
+
+    #[test]
+    fn scores_solutions_avoid_moves_and_partial_credit() {
+        let positions = parse_epd(&format!("{WAC_001}\n{STS_001}")).expect("parse epd");
+
+        let wac = start_position(&positions[0].fen).expect("position");
+        let best = san_move(&wac, "Qg6").expect("move");
+        let other = san_move(&wac, "Qh4").expect("move");
+        assert_eq!(score_move(&positions[0], &wac, &best), (true, 1, 1));
+        assert_eq!(score_move(&positions[0], &wac, &other), (false, 0, 1));
+
+        let sts = start_position(&positions[1].fen).expect("position");
+        let partial = san_move(&sts, "Bf2").expect("move");
+        assert_eq!(score_move(&positions[1], &sts, &partial), (false, 3, 10));
+
+        let avoid = EpdPosition {
+            best: Vec::new(),
+            avoid: vec!["Qh4".to_string()],
+            ..positions[0].clone()
+        };
+        assert_eq!(score_move(&avoid, &wac, &best), (true, 1, 1));
+        assert_eq!(score_move(&avoid, &wac, &other), (false, 0, 1));
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use shakmaty::{Chess, Color, Position};
+use shakmaty_syzygy::{AmbiguousWdl, Tablebase, Wdl};
+
+use crate::adjudication::Verdict;
+use crate::domain::Side;
+
+/// Syzygy tables loaded from a local directory, used to adjudicate endgames with at most
+/// `max_pieces` pieces on the board.
+#[derive(Debug)]
+pub struct Tablebases {
+    tables: Tablebase<Chess>,
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    /// Loads every table in `dir`. `max_pieces` defaults to the largest tables found, and
+    /// is capped by them.
+    pub fn open(dir: &Path, max_pieces: Option<usize>) -> Result<Self, String> {
+        let mut tables = Tablebase::new();
+        let count = tables
+            .add_directory(dir)
+            .map_err(|err| format!("failed to read syzygy tables from {}: {err}", dir.display()))?;
+        if count == 0 {
+            return Err(format!("no syzygy tables in {}", dir.display()));
+        }
+        let available = tables.max_pieces();
+        Ok(Tablebases { tables, max_pieces: max_pieces.map_or(available, |pieces| pieces.min(available)) })
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// The result of `pos` with best play under the 50-move rule, if it is small enough
+    /// and its tables are present. Wins that the 50-move rule frustrates count as draws.
+    /// Right after a capture or pawn move WDL tables suffice; later positions also need
+    /// DTZ tables, and are skipped when the rounding in those leaves the result open.
+    pub fn probe(&self, pos: &Chess) -> Option<Verdict> {
+        if pos.board().occupied().count() > self.max_pieces || pos.castles().any() {
+            return None;
+        }
+        let wdl = if pos.halfmoves() == 0 {
+            self.tables.probe_wdl_after_zeroing(pos).ok()?
+        } else {
+            self.tables.probe_wdl(pos).ok().and_then(AmbiguousWdl::unambiguous)?
+        };
+
+        let to_move = match pos.turn() {
+            Color::White => Side::White,
+            Color::Black => Side::Black,
+        };
+        Some(match wdl {
+            Wdl::Win => Verdict::Win(to_move),
+            Wdl::Loss => Verdict::Win(opponent(to_move)),
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => Verdict::Draw,
+        })
+    }
+}
+
+fn opponent(side: Side) -> Side {
+    match side {
+        Side::White => Side::Black,
+        Side::Black => Side::White,
+    }
+}
@@ -0,0 +1,599 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::adjudication::AdjudicationRules;
+use crate::chess960;
+use crate::domain::{ResultReason, TimeControl, Variant};
+use crate::engine::EngineSpec;
+use crate::headless::{load_openings, pair_start_fen};
+use crate::match_runner::{DEFAULT_STALL_TIMEOUT_MS, MatchConfig, play_game};
+use crate::pgn::{GameInfo, game_record};
+use crate::stats::{Score, Sprt, SprtDecision};
+use crate::syzygy::Tablebases;
+use crate::transcript::MatchTranscripts;
+
+const DEFAULT_SPRT_MAX_GAMES: u32 = 20_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TournamentFormat {
+    RoundRobin,
+    Gauntlet,
+    Sprt,
+}
+
+/// A tournament description, read from TOML. Relative paths are resolved against the
+/// directory of the tournament file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TournamentConfig {
+    /// Engine config file the players are taken from.
+    pub engines: PathBuf,
+    pub format: TournamentFormat,
+    /// Engine ids taking part. For SPRT the first is tested against the second.
+    pub players: Vec<String>,
+    /// Time control as `base+inc` in seconds.
+    pub tc: String,
+    /// Number of times every pairing plays a game pair (round-robin and gauntlet).
+    #[serde(default = "default_rounds")]
+    pub rounds: u32,
+    /// The engine that plays every other player in a gauntlet.
+    pub gauntlet: Option<String>,
+    pub sprt: Option<Sprt>,
+    /// Upper bound on the number of SPRT games.
+    pub max_games: Option<u32>,
+    pub openings: Option<PathBuf>,
+    pub pgn_out: PathBuf,
+    pub state_file: PathBuf,
+    pub crosstable_out: Option<PathBuf>,
+    #[serde(default = "default_event")]
+    pub event: String,
+    /// Stall watchdog for every game in milliseconds; zero disables it.
+    #[serde(default = "default_stall_timeout_ms")]
+    pub stall_timeout_ms: u64,
+    /// How far past its clock an engine may go before it forfeits, in milliseconds.
+    #[serde(default)]
+    pub timemargin_ms: u64,
+    /// Resign and draw rules applied to every game.
+    #[serde(default)]
+    pub adjudication: AdjudicationRules,
+    /// Directory of Syzygy tables to adjudicate endgames with.
+    pub syzygy_path: Option<PathBuf>,
+    /// Largest number of pieces adjudicated by the tables; all they cover by default.
+    pub syzygy_pieces: Option<usize>,
+    #[serde(default)]
+    pub variant: Variant,
+    /// Chess960 position for every game; without openings, each game pair gets its own
+    /// position otherwise.
+    pub chess960_index: Option<u32>,
+}
+
+fn default_rounds() -> u32 {
+    1
+}
+
+fn default_stall_timeout_ms() -> u64 {
+    DEFAULT_STALL_TIMEOUT_MS
+}
+
+fn default_event() -> String {
+    "chessbench tournament".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledGame {
+    pub white: String,
+    pub black: String,
+    pub opening: usize,
+}
+
+/// Progress checkpoint, rewritten after every game.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TournamentState {
+    pub games: Vec<FinishedGame>,
+    /// Draws the Chess960 position of each game pair, kept so that a resumed tournament
+    /// plays the same positions.
+    #[serde(default)]
+    pub chess960_seed: Option<u64>,
+    /// PGN record of the last finished game. The state is saved before the game is
+    /// appended to the PGN, so a resumed run can add it if the append never happened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_pgn: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinishedGame {
+    pub white: String,
+    pub black: String,
+    pub result: String,
+    pub reason: Option<ResultReason>,
+}
+
+impl TournamentConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let mut config: TournamentConfig =
+            toml::from_str(&text).map_err(|err| format!("invalid tournament config: {err}"))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.engines = base.join(&config.engines);
+        config.pgn_out = base.join(&config.pgn_out);
+        config.state_file = base.join(&config.state_file);
+        config.openings = config.openings.map(|openings| base.join(openings));
+        config.crosstable_out = config.crosstable_out.map(|out| base.join(out));
+        config.syzygy_path = config.syzygy_path.map(|dir| base.join(dir));
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn time_control(&self) -> Result<TimeControl, String> {
+        self.tc.parse()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.time_control()?;
+        self.adjudication.validate()?;
+
+        if let Some(index) = self.chess960_index {
+            chess960::start_fen(index)?;
+        }
+
+        if self.players.len() < 2 {
+            return Err("a tournament needs at least two players".to_string());
+        }
+        let mut seen = HashSet::new();
+        if let Some(dup) = self.players.iter().find(|id| !seen.insert(*id)) {
+            return Err(format!("duplicate player: {dup}"));
+        }
+
+        match self.format {
+            TournamentFormat::RoundRobin => {}
+            TournamentFormat::Gauntlet => match &self.gauntlet {
+                Some(id) if self.players.contains(id) => {}
+                Some(id) => return Err(format!("gauntlet engine {id} is not a player")),
+                None => return Err("gauntlet format needs a `gauntlet` engine id".to_string()),
+            },
+            TournamentFormat::Sprt => {
+                if self.players.len() != 2 {
+                    return Err("sprt format needs exactly two players".to_string());
+                }
+                let Some(sprt) = &self.sprt else {
+                    return Err("sprt format needs an [sprt] section".to_string());
+                };
+                if sprt.elo0 >= sprt.elo1 {
+                    return Err("sprt elo0 must be below elo1".to_string());
+                }
+                if !(0.0..1.0).contains(&sprt.alpha) || !(0.0..1.0).contains(&sprt.beta) {
+                    return Err("sprt alpha and beta must be between 0 and 1".to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The full, deterministic list of games. Every pairing is played as a game pair
+    /// with colours reversed on the same opening, so a resumed run sees the same games
+    /// in the same order.
+    pub fn schedule(&self) -> Vec<ScheduledGame> {
+        let pairings: Vec<(&String, &String)> = match self.format {
+            TournamentFormat::RoundRobin => {
+                let mut pairings = Vec::new();
+                for (idx, first) in self.players.iter().enumerate() {
+                    for second in &self.players[idx + 1..] {
+                        pairings.push((first, second));
+                    }
+                }
+                pairings
+            }
+            TournamentFormat::Gauntlet => {
+                let challenger = self.gauntlet.as_ref().expect("validated gauntlet engine");
+                self.players
+                    .iter()
+                    .filter(|id| *id != challenger)
+                    .map(|opponent| (challenger, opponent))
+                    .collect()
+            }
+            TournamentFormat::Sprt => vec![(&self.players[0], &self.players[1])],
+        };
+
+        let pairs_to_play = match self.format {
+            TournamentFormat::Sprt => self.max_games.unwrap_or(DEFAULT_SPRT_MAX_GAMES).div_ceil(2) as usize,
+            _ => pairings.len() * self.rounds as usize,
+        };
+
+        let mut games = Vec::with_capacity(pairs_to_play * 2);
+        for pair in 0..pairs_to_play {
+            let (first, second) = pairings[pair % pairings.len()];
+            games.push(ScheduledGame {
+                white: first.clone(),
+                black: second.clone(),
+                opening: pair,
+            });
+            games.push(ScheduledGame {
+                white: second.clone(),
+                black: first.clone(),
+                opening: pair,
+            });
+        }
+        games
+    }
+}
+
+impl TournamentState {
+    fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(TournamentState::default());
+        }
+        let text = fs::read_to_string(path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        serde_json::from_str(&text).map_err(|err| format!("invalid state file {}: {err}", path.display()))
+    }
+
+    /// Writes the state next to its final location and renames it into place, so an
+    /// interruption never leaves a half-written checkpoint behind.
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| format!("failed to encode state: {err}"))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json).map_err(|err| format!("failed to write {}: {err}", tmp.display()))?;
+        fs::rename(&tmp, path).map_err(|err| format!("failed to write {}: {err}", path.display()))
+    }
+
+    fn score_of(&self, player: &str, opponent: Option<&str>) -> Score {
+        let mut score = Score::default();
+        for game in &self.games {
+            let as_white = game.white == player && opponent.is_none_or(|o| game.black == o);
+            let as_black = game.black == player && opponent.is_none_or(|o| game.white == o);
+            if as_white || as_black {
+                score.record(&game.result, as_white);
+            }
+        }
+        score
+    }
+}
+
+/// Runs (or resumes) a tournament, checkpointing after every game. `on_game` is called
+/// with the game's number, the number of scheduled games and the game as each finishes.
+pub async fn run_tournament(
+    config: &TournamentConfig,
+    engines: &[EngineSpec],
+    mut on_game: impl FnMut(usize, usize, &FinishedGame),
+) -> Result<TournamentState, String> {
+    let time_control = config.time_control()?;
+    let players: Vec<&EngineSpec> = config
+        .players
+        .iter()
+        .map(|id| {
+            engines
+                .iter()
+                .find(|engine| &engine.id == id)
+                .ok_or_else(|| format!("unknown or unavailable engine id: {id}"))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let openings = match &config.openings {
+        Some(path) => load_openings(path, config.variant)?,
+        None => Vec::new(),
+    };
+
+    let tablebases = match &config.syzygy_path {
+        Some(dir) => Some(Arc::new(Tablebases::open(dir, config.syzygy_pieces)?)),
+        None => None,
+    };
+    if let Some(tables) = &tablebases {
+        println!("Adjudicating positions with up to {} pieces by tablebase", tables.max_pieces());
+    }
+
+    let schedule = config.schedule();
+    let mut state = TournamentState::load(&config.state_file)?;
+    for (done, planned) in state.games.iter().zip(&schedule) {
+        if done.white != planned.white || done.black != planned.black {
+            return Err(format!(
+                "state file {} does not match the tournament config",
+                config.state_file.display()
+            ));
+        }
+    }
+    let seed = *state.chess960_seed.get_or_insert_with(chess960::random_seed);
+    if !state.games.is_empty() {
+        println!("Resuming after {} finished games", state.games.len());
+    }
+
+    let mut pgn_file = open_pgn(&config.pgn_out, &state)?;
+
+    let total = schedule.len();
+    for (idx, planned) in schedule.iter().enumerate().skip(state.games.len()) {
+        if config.format == TournamentFormat::Sprt && sprt_finished(config, &state) {
+            break;
+        }
+
+        let white = players.iter().find(|p| p.id == planned.white).expect("scheduled player");
+        let black = players.iter().find(|p| p.id == planned.black).expect("scheduled player");
+        let start_fen = pair_start_fen(&openings, config.variant, config.chess960_index, seed, planned.opening)?;
+
+        println!("Started game {} of {total} ({} vs {})", idx + 1, white.id, black.id);
+
+        let match_config = MatchConfig {
+            white: (*white).clone(),
+            black: (*black).clone(),
+            time_control,
+            variant: config.variant,
+            start_fen: start_fen.clone(),
+            transcripts: MatchTranscripts::default(),
+            stall_timeout_ms: config.stall_timeout_ms,
+            timemargin_ms: config.timemargin_ms,
+            adjudication: config.adjudication,
+            tablebases: tablebases.clone(),
+            human: None,
+        };
+        let finished = play_game(format!("game-{}", idx + 1), match_config).await;
+
+        let info = GameInfo {
+            event: &config.event,
+            round: idx as u32 + 1,
+            white: &white.id,
+            black: &black.id,
+            time_control,
+        };
+        let record = game_record(&info, &finished, &finished.pgn, start_fen.is_none());
+        let result = finished.result.as_ref();
+        let game = FinishedGame {
+            white: white.id.clone(),
+            black: black.id.clone(),
+            result: result.map(|r| r.result.clone()).unwrap_or_else(|| "*".to_string()),
+            reason: result.map(|r| r.reason),
+        };
+        let reason = game.reason.map(|r| r.to_string()).unwrap_or_else(|| "unfinished".to_string());
+        println!("Finished game {} ({} vs {}): {} {{{reason}}}", idx + 1, game.white, game.black, game.result);
+
+        state.games.push(game);
+        state.last_pgn = Some(record.clone());
+        state.save(&config.state_file)?;
+        pgn_file
+            .write_all(record.as_bytes())
+            .map_err(|err| format!("failed to write pgn: {err}"))?;
+        on_game(idx + 1, total, state.games.last().expect("game just recorded"));
+
+        if config.format == TournamentFormat::Sprt {
+            print_sprt_progress(config, &state);
+        }
+    }
+
+    let table = crosstable(config, &state);
+    println!("{table}");
+    if let Some(path) = &config.crosstable_out {
+        fs::write(path, format!("{table}\n")).map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+    }
+
+    Ok(state)
+}
+
+/// Opens the PGN for appending after bringing it in line with the checkpoint: games a
+/// previous run wrote past its last saved state are cut off, and the last checkpointed
+/// game is added if the run stopped before writing it.
+fn open_pgn(path: &Path, state: &TournamentState) -> Result<File, String> {
+    let existing = match fs::read_to_string(path) {
+        Ok(text) if !state.games.is_empty() => text,
+        Ok(_) => String::new(),
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(format!("failed to read {}: {err}", path.display())),
+    };
+    let text = pgn_for_state(&existing, state);
+    if text != existing || !path.exists() {
+        fs::write(path, &text).map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+    }
+    OpenOptions::new()
+        .append(true)
+        .open(path)
+        .map_err(|err| format!("failed to open {}: {err}", path.display()))
+}
+
+fn pgn_for_state(existing: &str, state: &TournamentState) -> String {
+    let starts: Vec<usize> = existing
+        .match_indices("[Event ")
+        .map(|(idx, _)| idx)
+        .filter(|&idx| idx == 0 || existing[..idx].ends_with('\n'))
+        .collect();
+    let games = state.games.len();
+    let mut text = existing[..starts.get(games).copied().unwrap_or(existing.len())].to_string();
+    if let Some(record) = state.last_pgn.as_ref().filter(|_| starts.len() + 1 == games) {
+        text.push_str(record);
+    }
+    text
+}
+
+fn sprt_finished(config: &TournamentConfig, state: &TournamentState) -> bool {
+    let Some(sprt) = &config.sprt else {
+        return false;
+    };
+    sprt.decision(&state.score_of(&config.players[0], None)) != SprtDecision::Continue
+}
+
+fn print_sprt_progress(config: &TournamentConfig, state: &TournamentState) {
+    let Some(sprt) = &config.sprt else {
+        return;
+    };
+    let score = state.score_of(&config.players[0], None);
+    let (lower, upper) = sprt.bounds();
+    println!(
+        "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+        config.players[0],
+        config.players[1],
+        score.wins,
+        score.losses,
+        score.draws,
+        score.ratio().unwrap_or(0.0),
+        score.games()
+    );
+    println!("LLR: {:.2} ({lower:.2}, {upper:.2}) [{}, {}]", sprt.llr(&score), sprt.elo0, sprt.elo1);
+    match sprt.decision(&score) {
+        SprtDecision::AcceptH1 => println!("SPRT: H1 accepted"),
+        SprtDecision::AcceptH0 => println!("SPRT: H0 accepted"),
+        SprtDecision::Continue => {}
+    }
+}
+
+/// Players ranked by points, with their score against every opponent. Head-to-head
+/// matches also get an Elo estimate.
+pub fn crosstable(config: &TournamentConfig, state: &TournamentState) -> String {
+    let mut rows: Vec<(&String, Score)> = config
+        .players
+        .iter()
+        .map(|player| (player, state.score_of(player, None)))
+        .collect();
+    rows.sort_by(|a, b| b.1.points().total_cmp(&a.1.points()));
+
+    let width = config.players.iter().map(String::len).max().unwrap_or(0).max(6);
+    let mut table = format!("{:<4} {:<width$} {:>7} {:>6} {:>7}", "Rank", "Engine", "Points", "Games", "Score");
+    for (player, _) in &rows {
+        table.push_str(&format!(" {player:>width$}"));
+    }
+
+    for (rank, (player, score)) in rows.iter().enumerate() {
+        table.push_str(&format!(
+            "\n{:<4} {:<width$} {:>7.1} {:>6} {:>6.1}%",
+            rank + 1,
+            player,
+            score.points(),
+            score.games(),
+            score.ratio().unwrap_or(0.0) * 100.0
+        ));
+        for (opponent, _) in &rows {
+            let head_to_head = state.score_of(player, Some(opponent));
+            let cell = if opponent == player {
+                "-".to_string()
+            } else if head_to_head.games() == 0 {
+                ".".to_string()
+            } else {
+                format!("{:.1}/{}", head_to_head.points(), head_to_head.games())
+            };
+            table.push_str(&format!(" {cell:>width$}"));
+        }
+    }
+
+    if let [(leader, score), (other, _)] = rows.as_slice() {
+        match score.elo() {
+            Some((elo, margin)) => {
+                table.push_str(&format!("\n\nElo difference {leader} vs {other}: {elo:.1} +/- {margin:.1}"))
+            }
+            None => table.push_str(&format!("\n\nElo difference {leader} vs {other}: n/a")),
+        }
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(text: &str) -> TournamentConfig {
+        let config: TournamentConfig = toml::from_str(text).expect("parse tournament");
+        config.validate().expect("validate tournament");
+        config
+    }
+
+    #[test]
+    fn round_robin_plays_every_pairing_with_both_colours() {
+        let config = config(
+            r#"
+            engines = "engines.toml"
+            format = "round-robin"
+            players = ["a", "b", "c"]
+            tc = "1+0.01"
+            rounds = 2
+            pgn_out = "out.pgn"
+            state_file = "state.json"
+            "#,
+        );
+
+        let schedule = config.schedule();
+        assert_eq!(schedule.len(), 12);
+        assert_eq!((schedule[0].white.as_str(), schedule[0].black.as_str()), ("a", "b"));
+        assert_eq!((schedule[1].white.as_str(), schedule[1].black.as_str()), ("b", "a"));
+        assert_eq!(schedule[0].opening, schedule[1].opening);
+        assert_eq!((schedule[4].white.as_str(), schedule[4].black.as_str()), ("b", "c"));
+        assert_eq!((schedule[6].white.as_str(), schedule[6].black.as_str()), ("a", "b"));
+        assert_eq!(schedule[6].opening, 3);
+    }
+
+    #[test]
+    fn gauntlet_only_pairs_the_challenger() {
+        let config = config(
+            r#"
+            engines = "engines.toml"
+            format = "gauntlet"
+            players = ["dev", "a", "b"]
+            gauntlet = "dev"
+            tc = "1"
+            pgn_out = "out.pgn"
+            state_file = "state.json"
+            "#,
+        );
+
+        let schedule = config.schedule();
+        assert_eq!(schedule.len(), 4);
+        assert!(schedule.iter().all(|game| game.white == "dev" || game.black == "dev"));
+    }
+
+    #[test]
+    fn sprt_requires_bounds_and_two_players() {
+        let text = r#"
+            engines = "engines.toml"
+            format = "sprt"
+            players = ["dev", "base"]
+            tc = "1"
+            pgn_out = "out.pgn"
+            state_file = "state.json"
+        "#;
+        let config: TournamentConfig = toml::from_str(text).expect("parse tournament");
+        assert!(config.validate().is_err());
+
+        let config = super::tests::config(&format!("{text}\n[sprt]\nelo0 = 0\nelo1 = 5\n"));
+        assert_eq!(config.schedule().len(), DEFAULT_SPRT_MAX_GAMES as usize);
+    }
+
+    #[test]
+    fn zero_move_limits_are_rejected() {
+        let text = r#"
+            engines = "engines.toml"
+            format = "round-robin"
+            players = ["a", "b"]
+            tc = "1"
+            pgn_out = "out.pgn"
+            state_file = "state.json"
+        "#;
+        let config: TournamentConfig =
+            toml::from_str(&format!("{text}\n[adjudication]\nmax_plies = 0\n")).expect("parse tournament");
+        assert_eq!(config.validate(), Err("max_plies must be at least 1".to_string()));
+
+        super::tests::config(&format!("{text}\n[adjudication]\nmax_plies = 1\n"));
+    }
+
+    #[test]
+    fn resumed_pgn_matches_the_checkpoint() {
+        let record = |round: u32| format!("[Event \"t\"]\n[Round \"{round}\"]\n\n1. e4 e5 1/2-1/2\n\n");
+        let game = FinishedGame {
+            white: "a".to_string(),
+            black: "b".to_string(),
+            result: "1/2-1/2".to_string(),
+            reason: Some(ResultReason::Draw),
+        };
+        let state = TournamentState {
+            games: vec![game.clone(), game],
+            chess960_seed: None,
+            last_pgn: Some(record(2)),
+        };
+
+        // A game written after the last checkpoint is dropped so the replay isn't duplicated.
+        let ahead = format!("{}{}{}", record(1), record(2), record(3));
+        assert_eq!(pgn_for_state(&ahead, &state), format!("{}{}", record(1), record(2)));
+        // A checkpointed game that never reached the file is restored from the state.
+        assert_eq!(pgn_for_state(&record(1), &state), format!("{}{}", record(1), record(2)));
+        assert_eq!(pgn_for_state(&format!("{}{}", record(1), record(2)), &state), format!("{}{}", record(1), record(2)));
+    }
+}
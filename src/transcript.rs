@@ -0,0 +1,154 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Capacity of a transcript's live channel. Slow readers get `Lagged` and skip ahead.
+const LIVE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Written to the engine's stdin.
+    Sent,
+    /// Read from the engine's stdout.
+    Received,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptLine {
+    /// Milliseconds since the transcript was created.
+    pub elapsed_ms: u64,
+    pub direction: Direction,
+    pub text: String,
+}
+
+impl fmt::Display for TranscriptLine {
+    /// `   12.345 > go wtime 1000 btime 1000`, with `<` for engine output and `!` for stderr.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = match self.direction {
+            Direction::Sent => '>',
+            Direction::Received => '<',
+            Direction::Stderr => '!',
+        };
+        write!(f, "{:>9.3} {marker} {}", self.elapsed_ms as f64 / 1000.0, self.text)
+    }
+}
+
+/// Every line exchanged with one engine process, shared between the process that records
+/// it and whoever wants to read it. New lines are also published for live readers until
+/// the transcript is closed.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    inner: Arc<Mutex<TranscriptInner>>,
+    started: Instant,
+}
+
+#[derive(Debug)]
+struct TranscriptInner {
+    lines: Vec<TranscriptLine>,
+    live: Option<broadcast::Sender<TranscriptLine>>,
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(TranscriptInner {
+                lines: Vec::new(),
+                live: Some(broadcast::channel(LIVE_CAPACITY).0),
+            })),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Transcript {
+    pub fn record(&self, direction: Direction, text: &str) {
+        let line = TranscriptLine {
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            direction,
+            text: text.to_string(),
+        };
+        let mut inner = self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(live) = &inner.live {
+            let _ = live.send(line.clone());
+        }
+        inner.lines.push(line);
+    }
+
+    pub fn lines(&self) -> Vec<TranscriptLine> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).lines.clone()
+    }
+
+    /// The lines so far plus a receiver for every later line, taken under one lock so that
+    /// nothing falls between the two. The receiver is `None` once the transcript is closed.
+    pub fn subscribe(&self) -> (Vec<TranscriptLine>, Option<broadcast::Receiver<TranscriptLine>>) {
+        let inner = self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        (inner.lines.clone(), inner.live.as_ref().map(broadcast::Sender::subscribe))
+    }
+
+    /// The last `count` lines the engine wrote to stderr.
+    pub fn stderr_tail(&self, count: usize) -> Vec<String> {
+        let inner = self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut tail: Vec<String> = inner
+            .lines
+            .iter()
+            .rev()
+            .filter(|line| line.direction == Direction::Stderr)
+            .take(count)
+            .map(|line| line.text.clone())
+            .collect();
+        tail.reverse();
+        tail
+    }
+
+    /// Ends the live stream; lines recorded afterwards are only kept.
+    pub fn close(&self) {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).live = None;
+    }
+
+    /// The transcript as text, one line per entry.
+    pub fn to_text(&self) -> String {
+        self.lines().iter().map(|line| format!("{line}\n")).collect()
+    }
+}
+
+/// The transcripts of both engines in one game.
+#[derive(Debug, Clone, Default)]
+pub struct MatchTranscripts {
+    pub white: Transcript,
+    pub black: Transcript,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_lines_and_streams_until_closed() {
+        let transcript = Transcript::default();
+        transcript.record(Direction::Sent, "uci");
+
+        let (backlog, live) = transcript.subscribe();
+        let mut live = live.expect("live receiver");
+        transcript.record(Direction::Received, "uciok");
+        transcript.record(Direction::Stderr, "warning: no nnue");
+        transcript.close();
+        transcript.record(Direction::Sent, "quit");
+
+        assert_eq!(backlog.len(), 1);
+        assert_eq!(live.try_recv().expect("line").text, "uciok");
+        assert_eq!(live.try_recv().expect("line").direction, Direction::Stderr);
+        assert!(live.try_recv().is_err());
+        assert!(transcript.subscribe().1.is_none());
+        assert_eq!(transcript.stderr_tail(5), vec!["warning: no nnue"]);
+
+        let text = transcript.to_text();
+        let markers: Vec<&str> = text.lines().map(|line| line.split_whitespace().nth(1).unwrap_or("")).collect();
+        assert_eq!(markers, vec![">", "<", "!", ">"]);
+        assert!(text.lines().nth(1).is_some_and(|line| line.ends_with("< uciok")));
+    }
+}
@@ -1,24 +1,214 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use std::process::Stdio;
+use std::process::ExitStatus;
 use std::time::Duration;
 
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, ChildStdin, ChildStdout, Command};
-use tokio::time::timeout;
+use serde::{Deserialize, Serialize};
+use tokio::time::{Instant, timeout};
 
 use crate::config::EngineConfig;
-use crate::engine::EngineSpec;
+use crate::engine::{EngineProcess, EngineSpec};
+use crate::protocol::spawn_engine;
+use crate::transcript::Transcript;
 
 #[derive(Debug, Clone)]
 pub struct UciEngineInfo {
     pub name: String,
     pub author: String,
+    pub options: Vec<UciOption>,
+}
+
+/// An option advertised by the engine during the `uci` handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UciOption {
+    pub name: String,
+    pub kind: String,
+    pub default: Option<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub vars: Vec<String>,
+}
+
+impl UciEngineInfo {
+    /// Looks up an advertised option. UCI option names are case-insensitive.
+    pub fn option(&self, name: &str) -> Option<&UciOption> {
+        self.options.iter().find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Parses an `option name <name> type <kind> [default <x>] [min <n>] [max <n>] [var <v>]*`
+/// line. Names and values may contain spaces, so each field runs up to the next keyword.
+pub fn parse_option(line: &str) -> Option<UciOption> {
+    let rest = line.strip_prefix("option ")?;
+    let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+    for token in rest.split_whitespace() {
+        match token {
+            "name" | "type" | "default" | "min" | "max" | "var" => fields.push((token, Vec::new())),
+            _ => fields.last_mut()?.1.push(token),
+        }
+    }
+
+    let mut option = UciOption {
+        name: String::new(),
+        kind: String::new(),
+        default: None,
+        min: None,
+        max: None,
+        vars: Vec::new(),
+    };
+    for (key, words) in fields {
+        let value = words.join(" ");
+        match key {
+            "name" => option.name = value,
+            "type" => option.kind = value,
+            "default" => option.default = Some(value),
+            "min" => option.min = value.parse().ok(),
+            "max" => option.max = value.parse().ok(),
+            _ => option.vars.push(value),
+        }
+    }
+
+    if option.name.is_empty() || option.kind.is_empty() {
+        return None;
+    }
+    Some(option)
+}
+
+/// Engine evaluation from an `info` line, from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Score {
+    Cp(i32),
+    Mate(i32),
+}
+
+/// Centipawn value given to a mate score, less one per move until mate.
+pub const MATE_CP: i32 = 10_000;
+
+impl Score {
+    /// The score as centipawns, with mates counted as `MATE_CP` minus the moves to mate.
+    pub fn centipawns(self) -> i32 {
+        match self {
+            Score::Cp(cp) => cp,
+            Score::Mate(moves) if moves > 0 => MATE_CP - moves,
+            Score::Mate(moves) => -MATE_CP - moves,
+        }
+    }
+}
+
+/// The fields of a UCI `info` line that chessbench cares about.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    /// The score is only a `lowerbound` or `upperbound` of the true score.
+    pub bound: bool,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time_ms: Option<u64>,
+    pub pv: Vec<String>,
+}
+
+/// Parses an `info` line. Lines that carry neither a depth nor a score (`info string`,
+/// `info currmove`, ...) are not interesting to us and yield `None`.
+pub fn parse_info(line: &str) -> Option<SearchInfo> {
+    let rest = line.strip_prefix("info ")?;
+    let mut info = SearchInfo::default();
+    let mut tokens = rest.split_whitespace();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => info.depth = tokens.next().and_then(|v| v.parse().ok()),
+            "seldepth" => info.seldepth = tokens.next().and_then(|v| v.parse().ok()),
+            "multipv" => info.multipv = tokens.next().and_then(|v| v.parse().ok()),
+            "nodes" => info.nodes = tokens.next().and_then(|v| v.parse().ok()),
+            "nps" => info.nps = tokens.next().and_then(|v| v.parse().ok()),
+            "time" => info.time_ms = tokens.next().and_then(|v| v.parse().ok()),
+            "score" => {
+                info.score = match (tokens.next(), tokens.next().and_then(|v| v.parse().ok())) {
+                    (Some("cp"), Some(value)) => Some(Score::Cp(value)),
+                    (Some("mate"), Some(value)) => Some(Score::Mate(value)),
+                    _ => None,
+                };
+            }
+            "lowerbound" | "upperbound" => info.bound = true,
+            "pv" => {
+                info.pv = tokens.by_ref().map(str::to_string).collect();
+            }
+            "string" => return None,
+            _ => {}
+        }
+    }
+
+    if info.depth.is_none() && info.score.is_none() {
+        return None;
+    }
+
+    Some(info)
+}
+
+/// The latest `info` line for each `multipv` index of a search. Lines without a PV are
+/// ignored; an `info` line without `multipv` counts as line 1.
+#[derive(Debug, Clone, Default)]
+pub struct PvLines {
+    lines: BTreeMap<u32, SearchInfo>,
+}
+
+impl PvLines {
+    pub fn update(&mut self, info: &SearchInfo) {
+        if !info.pv.is_empty() {
+            self.lines.insert(info.multipv.unwrap_or(1), info.clone());
+        }
+    }
+
+    /// The best `count` lines, ordered by `multipv` index.
+    pub fn top(&self, count: usize) -> Vec<(u32, &SearchInfo)> {
+        self.lines.iter().take(count).map(|(multipv, info)| (*multipv, info)).collect()
+    }
+}
+
+/// Limits for a `go` command. Unset fields are left out of the command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movetime: Option<u64>,
+    pub depth: Option<u32>,
+}
+
+impl SearchLimits {
+    pub fn go_command(&self) -> String {
+        let mut command = String::from("go");
+        let fields = [
+            ("wtime", self.wtime),
+            ("btime", self.btime),
+            ("winc", self.winc),
+            ("binc", self.binc),
+            ("movetime", self.movetime),
+            ("depth", self.depth.map(u64::from)),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                command.push_str(&format!(" {name} {value}"));
+            }
+        }
+        if command == "go" {
+            command.push_str(" infinite");
+        }
+        command
+    }
 }
 
 #[derive(Debug)]
 pub enum UciError {
     Io(std::io::Error),
     Timeout(&'static str),
+    /// The engine stopped producing output and did not answer a ping.
+    Stalled,
     UnexpectedEof,
     InvalidResponse(String),
 }
@@ -28,6 +218,7 @@ impl std::fmt::Display for UciError {
         match self {
             UciError::Io(err) => write!(f, "io error: {err}"),
             UciError::Timeout(stage) => write!(f, "timeout waiting for {stage}"),
+            UciError::Stalled => write!(f, "engine stopped responding"),
             UciError::UnexpectedEof => write!(f, "unexpected EOF"),
             UciError::InvalidResponse(line) => write!(f, "invalid response: {line}"),
         }
@@ -41,42 +232,30 @@ impl From<std::io::Error> for UciError {
 }
 
 pub struct UciProcess {
-    child: Child,
-    stdin: ChildStdin,
-    stdout: BufReader<ChildStdout>,
+    process: EngineProcess,
 }
 
 impl UciProcess {
-    pub async fn spawn(path: &PathBuf, args: &[String], working_dir: Option<&PathBuf>) -> Result<Self, UciError> {
-        let mut command = Command::new(path);
-        command.args(args);
-        command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null());
-        if let Some(dir) = working_dir {
-            command.current_dir(dir);
-        }
-
-        let mut child = command.spawn()?;
-        let stdin = child.stdin.take().ok_or(UciError::UnexpectedEof)?;
-        let stdout = child.stdout.take().ok_or(UciError::UnexpectedEof)?;
-        let stdout = BufReader::new(stdout);
-
-        Ok(Self { child, stdin, stdout })
+    pub async fn spawn(
+        path: &PathBuf,
+        args: &[String],
+        working_dir: Option<&PathBuf>,
+        transcript: Transcript,
+    ) -> Result<Self, UciError> {
+        let process = EngineProcess::spawn(path, args, working_dir, transcript).await?;
+        Ok(Self { process })
     }
 
     pub async fn send_line(&mut self, line: &str) -> Result<(), UciError> {
-        self.stdin.write_all(line.as_bytes()).await?;
-        self.stdin.write_all(b"\n").await?;
-        self.stdin.flush().await?;
-        Ok(())
+        self.process.send_line(line).await
+    }
+
+    pub async fn exit_status(&mut self) -> Option<ExitStatus> {
+        self.process.exit_status().await
     }
 
     async fn read_line(&mut self) -> Result<String, UciError> {
-        let mut buf = String::new();
-        let bytes = self.stdout.read_line(&mut buf).await?;
-        if bytes == 0 {
-            return Err(UciError::UnexpectedEof);
-        }
-        Ok(buf.trim().to_string())
+        self.process.read_line().await
     }
 
     pub async fn handshake(&mut self) -> Result<UciEngineInfo, UciError> {
@@ -84,6 +263,7 @@ impl UciProcess {
 
         let mut name = None;
         let mut author = None;
+        let mut options = Vec::new();
 
         loop {
             let line = self.read_line().await?;
@@ -91,6 +271,8 @@ impl UciProcess {
                 name = Some(rest.trim().to_string());
             } else if let Some(rest) = line.strip_prefix("id author ") {
                 author = Some(rest.trim().to_string());
+            } else if let Some(option) = parse_option(&line) {
+                options.push(option);
             } else if line == "uciok" {
                 break;
             }
@@ -99,9 +281,22 @@ impl UciProcess {
         Ok(UciEngineInfo {
             name: name.unwrap_or_else(|| "".to_string()),
             author: author.unwrap_or_else(|| "".to_string()),
+            options,
         })
     }
 
+    pub async fn set_option(&mut self, name: &str, value: &str) -> Result<(), UciError> {
+        self.send_line(&format!("setoption name {name} value {value}")).await
+    }
+
+    /// Sends every configured option. Call after the handshake and before `isready`.
+    pub async fn configure(&mut self, options: &[(String, String)]) -> Result<(), UciError> {
+        for (name, value) in options {
+            self.set_option(name, value).await?;
+        }
+        Ok(())
+    }
+
     pub async fn is_ready(&mut self) -> Result<(), UciError> {
         self.send_line("isready").await?;
         loop {
@@ -117,27 +312,35 @@ impl UciProcess {
         self.send_line("ucinewgame").await
     }
 
-    pub async fn bestmove(&mut self, wtime: u64, btime: u64, timeout_ms: u64) -> Result<String, UciError> {
-        self.send_line(&format!("go wtime {wtime} btime {btime}")).await?;
+    pub async fn bestmove(
+        &mut self,
+        limits: &SearchLimits,
+        timeout_ms: u64,
+        mut on_info: impl FnMut(SearchInfo),
+    ) -> Result<String, UciError> {
+        self.send_line(&limits.go_command()).await?;
 
-        let deadline = Duration::from_millis(timeout_ms);
-        let line = timeout(deadline, async {
-            loop {
-                let line = self.read_line().await?;
-                if let Some(rest) = line.strip_prefix("bestmove ") {
-                    return Ok::<String, UciError>(rest.trim().to_string());
-                }
+        // `isready` must be answered even mid-search, which makes it the stall ping.
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            let Some(line) = self.process.read_search_line(deadline, Some("isready")).await? else {
+                return Err(UciError::Timeout("bestmove"));
+            };
+            if let Some(rest) = line.strip_prefix("bestmove ") {
+                return Ok(rest.split_whitespace().next().unwrap_or("").to_string());
             }
-        })
-        .await
-        .map_err(|_| UciError::Timeout("bestmove"))??;
+            if let Some(info) = parse_info(&line) {
+                on_info(info);
+            }
+        }
+    }
 
-        Ok(line)
+    pub fn set_stall_timeout(&mut self, stall_timeout: Option<Duration>) {
+        self.process.set_stall_timeout(stall_timeout);
     }
 
-    pub async fn quit(mut self) -> Result<(), UciError> {
-        let _ = self.send_line("quit").await;
-        let _ = self.child.wait().await;
+    pub async fn quit(self) -> Result<(), UciError> {
+        self.process.quit("quit").await;
         Ok(())
     }
 }
@@ -146,7 +349,9 @@ pub async fn discover_engines(configs: &[EngineConfig]) -> Result<Vec<EngineSpec
     let mut engines = Vec::new();
 
     for entry in configs {
-        let mut process = match UciProcess::spawn(&entry.path, &entry.args, entry.working_dir.as_ref()).await {
+        let working_dir = entry.working_dir.as_ref();
+        let spawned = spawn_engine(entry.protocol, &entry.path, &entry.args, working_dir, Transcript::default()).await;
+        let mut process = match spawned {
             Ok(process) => process,
             Err(err) => {
                 eprintln!("failed to spawn engine {}: {err}", entry.id);
@@ -157,19 +362,26 @@ pub async fn discover_engines(configs: &[EngineConfig]) -> Result<Vec<EngineSpec
         let info = match timeout(Duration::from_secs(5), process.handshake()).await {
             Ok(Ok(info)) => info,
             Ok(Err(err)) => {
-                eprintln!("uci handshake failed for {}: {err}", entry.id);
-                let _ = process.quit().await;
+                eprintln!("handshake failed for {}: {err}", entry.id);
+                process.quit().await;
                 continue;
             }
             Err(_) => {
-                eprintln!("uci handshake timeout for {}", entry.id);
-                let _ = process.quit().await;
+                eprintln!("handshake timeout for {}", entry.id);
+                process.quit().await;
                 continue;
             }
         };
 
         let _ = process.is_ready().await;
-        let _ = process.quit().await;
+        process.quit().await;
+
+        let options = entry.option_values();
+        for (option, _) in &options {
+            if info.option(option).is_none() {
+                eprintln!("engine {} has no option {option}; it will be sent anyway", entry.id);
+            }
+        }
 
         engines.push(EngineSpec {
             id: entry.id.clone(),
@@ -178,8 +390,106 @@ pub async fn discover_engines(configs: &[EngineConfig]) -> Result<Vec<EngineSpec
             path: entry.path.clone(),
             args: entry.args.clone(),
             working_dir: entry.working_dir.clone(),
+            protocol: entry.protocol,
+            options,
         });
     }
 
     Ok(engines)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_info_with_score_and_pv() {
+        let line = "info depth 12 seldepth 18 multipv 1 score cp -34 nodes 123456 nps 987000 time 125 pv e2e4 e7e5 g1f3";
+        let info = parse_info(line).expect("info line");
+
+        assert_eq!(info.depth, Some(12));
+        assert_eq!(info.seldepth, Some(18));
+        assert_eq!(info.multipv, Some(1));
+        assert_eq!(info.score, Some(Score::Cp(-34)));
+        assert_eq!(info.nodes, Some(123456));
+        assert_eq!(info.nps, Some(987000));
+        assert_eq!(info.time_ms, Some(125));
+        assert_eq!(info.pv, vec!["e2e4", "e7e5", "g1f3"]);
+    }
+
+    #[test]
+    fn parses_mate_scores_and_bounds() {
+        let info = parse_info("info depth 30 score mate -3 lowerbound nodes 10").expect("info line");
+        assert_eq!(info.score, Some(Score::Mate(-3)));
+        assert!(info.bound);
+        assert_eq!(info.nodes, Some(10));
+        assert!(!parse_info("info depth 30 score cp 15").expect("info line").bound);
+    }
+
+    #[test]
+    fn builds_go_command_from_limits() {
+        let limits = SearchLimits {
+            wtime: Some(1000),
+            btime: Some(900),
+            winc: Some(10),
+            binc: Some(10),
+            ..SearchLimits::default()
+        };
+        assert_eq!(limits.go_command(), "go wtime 1000 btime 900 winc 10 binc 10");
+
+        let depth = SearchLimits {
+            depth: Some(12),
+            ..SearchLimits::default()
+        };
+        assert_eq!(depth.go_command(), "go depth 12");
+    }
+
+    #[test]
+    fn groups_info_lines_by_multipv() {
+        let mut lines = PvLines::default();
+        for line in [
+            "info depth 10 multipv 1 score cp 30 pv e2e4 e7e5",
+            "info depth 10 multipv 2 score cp 20 pv d2d4 d7d5",
+            "info depth 10 multipv 3 score cp 10 pv g1f3",
+            "info depth 11 multipv 1 score cp 35 pv e2e4 c7c5",
+            "info depth 11 currmove d2d4 currmovenumber 2",
+        ] {
+            if let Some(info) = parse_info(line) {
+                lines.update(&info);
+            }
+        }
+
+        let top = lines.top(2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].0, 1);
+        assert_eq!(top[0].1.score, Some(Score::Cp(35)));
+        assert_eq!(top[0].1.pv, vec!["e2e4", "c7c5"]);
+        assert_eq!(top[1].1.pv, vec!["d2d4", "d7d5"]);
+        assert_eq!(lines.top(5).len(), 3);
+    }
+
+    #[test]
+    fn parses_option_lines() {
+        let hash = parse_option("option name Hash type spin default 16 min 1 max 33554432").expect("option");
+        assert_eq!(hash.name, "Hash");
+        assert_eq!(hash.kind, "spin");
+        assert_eq!(hash.default.as_deref(), Some("16"));
+        assert_eq!((hash.min, hash.max), (Some(1), Some(33554432)));
+
+        let style = parse_option("option name Play Style type combo default Normal var Solid var Normal").expect("option");
+        assert_eq!(style.name, "Play Style");
+        assert_eq!(style.vars, vec!["Solid", "Normal"]);
+
+        let button = parse_option("option name Clear Hash type button").expect("option");
+        assert_eq!(button.default, None);
+
+        assert_eq!(parse_option("option type spin"), None);
+    }
+
+    #[test]
+    fn skips_info_lines_without_search_data() {
+        assert_eq!(parse_info("info string NNUE evaluation enabled"), None);
+        assert_eq!(parse_info("info currmove e2e4 currmovenumber 1"), None);
+        assert_eq!(parse_info("bestmove e2e4"), None);
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use shakmaty::san::San;
+use shakmaty::uci::UciMove;
+use shakmaty::variant::VariantPosition;
+use shakmaty::{Color, Move, Position};
+use tokio::time::{Instant, timeout};
+
+use crate::domain::{TimeControl, Variant};
+use crate::engine::EngineProcess;
+use crate::match_runner::start_position_for;
+use crate::transcript::Transcript;
+use crate::uci::{Score, SearchInfo, SearchLimits, UciEngineInfo, UciError, UciOption};
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// How long to wait for more `feature` lines when the engine has not sent `done=0`.
+const FEATURE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A CECP (XBoard, protocol version 2) engine. The engine is kept in force mode between
+/// searches: before each `go` it is sent the moves it has not seen yet, its clocks and the
+/// search limits, and once it has moved it is put back into force mode.
+pub struct XboardProcess {
+    process: EngineProcess,
+    features: HashMap<String, String>,
+    ping: u32,
+    /// The start position and number of moves the engine's board currently holds.
+    synced: Option<(String, usize)>,
+    /// Set by the `UCI_Chess960` and `UCI_Variant` options and sent as `variant` with every
+    /// new game.
+    variant: Variant,
+}
+
+impl XboardProcess {
+    pub async fn spawn(
+        path: &PathBuf,
+        args: &[String],
+        working_dir: Option<&PathBuf>,
+        transcript: Transcript,
+    ) -> Result<Self, UciError> {
+        let process = EngineProcess::spawn(path, args, working_dir, transcript).await?;
+        Ok(Self { process, features: HashMap::new(), ping: 0, synced: None, variant: Variant::Standard })
+    }
+
+    pub async fn send_line(&mut self, line: &str) -> Result<(), UciError> {
+        self.process.send_line(line).await
+    }
+
+    pub fn set_stall_timeout(&mut self, stall_timeout: Option<Duration>) {
+        self.process.set_stall_timeout(stall_timeout);
+    }
+
+    pub async fn exit_status(&mut self) -> Option<ExitStatus> {
+        self.process.exit_status().await
+    }
+
+    fn feature(&self, name: &str) -> bool {
+        self.features.get(name).is_some_and(|value| value == "1")
+    }
+
+    /// Selects the variant after `new`, which resets engines to normal chess.
+    async fn send_variant(&mut self) -> Result<(), UciError> {
+        if self.variant == Variant::Standard {
+            return Ok(());
+        }
+        let names = xboard_variant_names(self.variant);
+        let supported: Vec<&str> = self
+            .features
+            .get("variants")
+            .map(|variants| variants.split(',').map(str::trim).collect())
+            .unwrap_or_default();
+        let Some(name) = names.iter().find(|name| supported.contains(name)) else {
+            return Err(UciError::InvalidResponse(format!(
+                "engine does not support variant {}",
+                names.join("/")
+            )));
+        };
+        self.send_line(&format!("variant {name}")).await
+    }
+
+    /// Sends `xboard` and `protover 2` and accepts the engine's features. Feature lines
+    /// are collected until `done=1`, or until the engine goes quiet if it never sent
+    /// `done=0`.
+    pub async fn handshake(&mut self) -> Result<UciEngineInfo, UciError> {
+        self.send_line("xboard").await?;
+        self.send_line("protover 2").await?;
+
+        let mut waiting_for_done = false;
+        let mut options = Vec::new();
+        loop {
+            let line = if waiting_for_done {
+                self.process.read_line().await?
+            } else {
+                match timeout(FEATURE_TIMEOUT, self.process.read_line()).await {
+                    Ok(line) => line?,
+                    Err(_) => break,
+                }
+            };
+
+            let Some(rest) = line.strip_prefix("feature ") else {
+                continue;
+            };
+            let mut done = false;
+            for (name, value) in parse_features(rest) {
+                match name.as_str() {
+                    "done" => {
+                        waiting_for_done = value == "0";
+                        done = value == "1";
+                    }
+                    "option" => options.extend(parse_option_feature(&value)),
+                    _ => {}
+                }
+                let known = matches!(
+                    name.as_str(),
+                    "done"
+                        | "myname"
+                        | "option"
+                        | "ping"
+                        | "setboard"
+                        | "usermove"
+                        | "san"
+                        | "sigint"
+                        | "sigterm"
+                        | "time"
+                        | "draw"
+                        | "reuse"
+                        | "analyze"
+                        | "colors"
+                        | "variants"
+                        | "name"
+                        | "debug"
+                        | "memory"
+                        | "smp"
+                        | "playother"
+                        | "ics"
+                        | "pause"
+                        | "nps"
+                        | "egt"
+                        | "exclude"
+                        | "setscore"
+                        | "highlight"
+                );
+                let reply = if known { "accepted" } else { "rejected" };
+                self.send_line(&format!("{reply} {name}")).await?;
+                self.features.insert(name, value);
+            }
+            if done {
+                break;
+            }
+        }
+
+        Ok(UciEngineInfo {
+            name: self.features.get("myname").cloned().unwrap_or_default(),
+            author: String::new(),
+            options,
+        })
+    }
+
+    /// `ping`/`pong` round trip when the engine supports it.
+    pub async fn is_ready(&mut self) -> Result<(), UciError> {
+        if !self.feature("ping") {
+            return Ok(());
+        }
+        self.ping += 1;
+        let pong = format!("pong {}", self.ping);
+        self.send_line(&format!("ping {}", self.ping)).await?;
+        loop {
+            if self.process.read_line().await? == pong {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sends configured options. `Hash` and `Threads` map to the `memory` and `cores`
+    /// commands for engines that asked for them with `memory=1` and `smp=1`;
+    /// `UCI_Chess960` and `UCI_Variant` select the variant for the next game; everything
+    /// else is sent as `option NAME=VALUE`.
+    pub async fn configure(&mut self, options: &[(String, String)]) -> Result<(), UciError> {
+        for (name, value) in options {
+            let command = if name.eq_ignore_ascii_case("hash") && self.feature("memory") {
+                format!("memory {value}")
+            } else if name.eq_ignore_ascii_case("threads") && self.feature("smp") {
+                format!("cores {value}")
+            } else if name.eq_ignore_ascii_case("UCI_Chess960") {
+                self.variant = if value == "true" { Variant::Chess960 } else { Variant::Standard };
+                continue;
+            } else if name.eq_ignore_ascii_case("UCI_Variant") {
+                self.variant = value.parse().map_err(UciError::InvalidResponse)?;
+                continue;
+            } else {
+                format!("option {name}={value}")
+            };
+            self.send_line(&command).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn new_game(&mut self, time_control: Option<TimeControl>) -> Result<(), UciError> {
+        self.send_line("new").await?;
+        self.send_variant().await?;
+        self.send_line("force").await?;
+        self.send_line("easy").await?;
+        self.send_line("post").await?;
+        if let Some(tc) = time_control {
+            self.send_line(&level_command(tc)).await?;
+        }
+        self.synced = None;
+        self.is_ready().await
+    }
+
+    /// Brings the engine's board up to date, sets its clocks and limits and lets it move.
+    /// Returns the move in UCI notation, or `resign`.
+    pub async fn search(
+        &mut self,
+        start_fen: &str,
+        moves: &[String],
+        limits: &SearchLimits,
+        timeout_ms: u64,
+        on_info: &mut (dyn FnMut(SearchInfo) + Send),
+    ) -> Result<String, UciError> {
+        let mut pos = start_position_for(start_fen, self.variant).map_err(UciError::InvalidResponse)?;
+        let sent = match &self.synced {
+            Some((fen, count)) if fen == start_fen && *count <= moves.len() => *count,
+            _ => {
+                if start_fen != START_FEN || self.variant != Variant::Standard {
+                    if !self.feature("setboard") {
+                        return Err(UciError::InvalidResponse("engine does not support setboard".to_string()));
+                    }
+                    self.send_line(&format!("setboard {start_fen}")).await?;
+                } else {
+                    self.send_line("new").await?;
+                    self.send_line("force").await?;
+                }
+                0
+            }
+        };
+        for (idx, uci) in moves.iter().enumerate() {
+            let mv = uci
+                .parse::<UciMove>()
+                .ok()
+                .and_then(|parsed| parsed.to_move(&pos).ok())
+                .ok_or_else(|| UciError::InvalidResponse(format!("illegal move {uci}")))?;
+            if idx >= sent {
+                let text = match mv {
+                    _ if self.feature("san") => San::from_move(&pos, &mv).to_string(),
+                    // Castling in Chess960 is only unambiguous in SAN.
+                    Move::Castle { king, rook } if self.variant == Variant::Chess960 => {
+                        if rook.file() > king.file() { "O-O".to_string() } else { "O-O-O".to_string() }
+                    }
+                    _ => uci.clone(),
+                };
+                let command = if self.feature("usermove") { format!("usermove {text}") } else { text };
+                self.send_line(&command).await?;
+            }
+            pos.play_unchecked(&mv);
+        }
+
+        // `time=0` engines keep their own clocks and are not sent `time`/`otim`.
+        let clocks = self.features.get("time").is_none_or(|value| value != "0");
+        for command in limit_commands(limits, pos.turn(), clocks) {
+            self.send_line(&command).await?;
+        }
+        self.send_line("go").await?;
+
+        // Engines that support `ping` answer it while thinking; others may think in silence
+        // until their clock runs out.
+        let ping = self.feature("ping").then(|| {
+            self.ping += 1;
+            format!("ping {}", self.ping)
+        });
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let reply = loop {
+            let Some(line) = self.process.read_search_line(deadline, ping.as_deref()).await? else {
+                return Err(UciError::Timeout("move"));
+            };
+            if let Some(text) = line.strip_prefix("move ") {
+                break text.trim().to_string();
+            }
+            if line == "resign" {
+                break line;
+            }
+            if let Some(info) = parse_thinking(&line, &pos) {
+                on_info(info);
+            }
+        };
+
+        if reply == "resign" {
+            return Ok(reply);
+        }
+        let uci = move_to_uci(&reply, &pos).ok_or(UciError::InvalidResponse(reply))?;
+        self.send_line("force").await?;
+        self.synced = Some((start_fen.to_string(), moves.len() + 1));
+        Ok(uci)
+    }
+
+    pub async fn quit(self) {
+        self.process.quit("quit").await;
+    }
+}
+
+/// Splits `name=value` pairs; values may be quoted and contain spaces.
+fn parse_features(text: &str) -> Vec<(String, String)> {
+    let mut features = Vec::new();
+    let mut rest = text.trim();
+    while let Some((name, after)) = rest.split_once('=') {
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, remaining)) => (value, remaining),
+                None => (quoted, ""),
+            },
+            None => after.split_once(' ').unwrap_or((after, "")),
+        };
+        features.push((name.trim().to_string(), value.to_string()));
+        rest = remaining.trim();
+    }
+    features
+}
+
+/// `feature option="Hash -spin 64 1 4096"` describes an engine option.
+fn parse_option_feature(value: &str) -> Option<UciOption> {
+    let (name, rest) = value.split_once(" -")?;
+    let mut fields = rest.split_whitespace();
+    let kind = fields.next()?.to_string();
+    let default = fields.next().map(str::to_string);
+    let (min, max) = if kind == "spin" || kind == "slider" {
+        (fields.next().and_then(|v| v.parse().ok()), fields.next().and_then(|v| v.parse().ok()))
+    } else {
+        (None, None)
+    };
+    Some(UciOption { name: name.trim().to_string(), kind, default, min, max, vars: Vec::new() })
+}
+
+/// `level MPS BASE INC`, with the base in minutes (or `minutes:seconds`) and the increment
+/// in seconds.
+fn level_command(tc: TimeControl) -> String {
+    let seconds = tc.initial_ms / 1000;
+    let base =
+        if seconds.is_multiple_of(60) { format!("{}", seconds / 60) } else { format!("{}:{:02}", seconds / 60, seconds % 60) };
+    let increment = tc.increment_ms as f64 / 1000.0;
+    format!("level 0 {base} {increment}")
+}
+
+/// Clock and limit commands for the side to move: `time`/`otim` in centiseconds when
+/// `clocks` is set, `st` in whole seconds and `sd` for a depth limit.
+fn limit_commands(limits: &SearchLimits, turn: Color, clocks: bool) -> Vec<String> {
+    let mut commands = Vec::new();
+    let (own, other) = match turn {
+        _ if !clocks => (None, None),
+        Color::White => (limits.wtime, limits.btime),
+        Color::Black => (limits.btime, limits.wtime),
+    };
+    if let Some(own) = own {
+        commands.push(format!("time {}", own / 10));
+    }
+    if let Some(other) = other {
+        commands.push(format!("otim {}", other / 10));
+    }
+    if let Some(movetime) = limits.movetime {
+        commands.push(format!("st {}", movetime.div_ceil(1000).max(1)));
+    }
+    if let Some(depth) = limits.depth {
+        commands.push(format!("sd {depth}"));
+    }
+    commands
+}
+
+/// Engines send coordinate moves (`e2e4`, `e7e8q`) but some use SAN or `O-O`. Castling is
+/// returned in the notation of the position's castling mode.
+fn move_to_uci(text: &str, pos: &VariantPosition) -> Option<String> {
+    let mv = match text.parse::<UciMove>().ok().and_then(|uci| uci.to_move(pos).ok()) {
+        Some(mv) => mv,
+        None => text.parse::<San>().ok()?.to_move(pos).ok()?,
+    };
+    Some(UciMove::from_move(&mv, pos.castles().mode()).to_string())
+}
+
+/// Names an XBoard engine may list in its `variants` feature for a variant, most common
+/// first. Antichess goes by the ICC and FICS names of its rule sets.
+fn xboard_variant_names(variant: Variant) -> &'static [&'static str] {
+    match variant {
+        Variant::Standard => &["normal"],
+        Variant::Chess960 => &["fischerandom"],
+        Variant::Atomic => &["atomic"],
+        Variant::Crazyhouse => &["crazyhouse"],
+        Variant::ThreeCheck => &["3check", "threecheck"],
+        Variant::KingOfTheHill => &["kingofthehill", "koth"],
+        Variant::Antichess => &["giveaway", "suicide", "antichess"],
+        Variant::Horde => &["horde"],
+        Variant::RacingKings => &["racingkings"],
+    }
+}
+
+/// Converts an XBoard score to a `Score`. Engines report mates either as 100000 plus
+/// the moves to mate, or as 32767 minus the plies to mate; both are negated when
+/// getting mated.
+fn xboard_score(score: i32) -> Score {
+    let moves_to_mate = match score.unsigned_abs() {
+        abs @ 100_000..=199_999 => abs - 100_000,
+        abs @ 32_000..=32_767 => (32_767 - abs).div_ceil(2).max(1),
+        _ => return Score::Cp(score),
+    } as i32;
+    Score::Mate(if score > 0 { moves_to_mate } else { -moves_to_mate })
+}
+
+/// Parses a thinking line, `ply score time nodes pv...`, with the time in centiseconds.
+/// The PV is converted to UCI moves as far as it is legal.
+fn parse_thinking(line: &str, pos: &VariantPosition) -> Option<SearchInfo> {
+    let mut tokens = line.split_whitespace();
+    let depth = tokens.next()?.trim_end_matches(['.', '&']).parse().ok()?;
+    let score = tokens.next()?.parse().ok()?;
+    let time_cs: u64 = tokens.next()?.parse().ok()?;
+    let nodes = tokens.next()?.parse().ok()?;
+
+    let mut pv = Vec::new();
+    let mut board = pos.clone();
+    for token in tokens {
+        if token.ends_with('.') || token.chars().all(|ch| ch.is_ascii_digit() || ch == '.') {
+            continue;
+        }
+        let Some(uci) = move_to_uci(token, &board) else {
+            break;
+        };
+        let Some(mv) = uci.parse::<UciMove>().ok().and_then(|parsed| parsed.to_move(&board).ok()) else {
+            break;
+        };
+        board.play_unchecked(&mv);
+        pv.push(uci);
+    }
+
+    Some(SearchInfo {
+        depth: Some(depth),
+        score: Some(xboard_score(score)),
+        nodes: Some(nodes),
+        time_ms: Some(time_cs * 10),
+        nps: (time_cs > 0).then(|| nodes * 100 / time_cs),
+        pv,
+        ..SearchInfo::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_feature_lines() {
+        let features =
+            parse_features(r#"myname="Crafty 25.2" setboard=1 usermove=1 option="Hash -spin 64 1 4096" done=1"#);
+        assert_eq!(features[0], ("myname".to_string(), "Crafty 25.2".to_string()));
+        assert_eq!(features[1], ("setboard".to_string(), "1".to_string()));
+        assert_eq!(features[4], ("done".to_string(), "1".to_string()));
+
+        let option = parse_option_feature(&features[3].1).expect("option");
+        assert_eq!(option.name, "Hash");
+        assert_eq!(option.kind, "spin");
+        assert_eq!((option.min, option.max), (Some(1), Some(4096)));
+    }
+
+    #[test]
+    fn builds_level_and_clock_commands() {
+        assert_eq!(level_command(TimeControl { initial_ms: 300_000, increment_ms: 2_000 }), "level 0 5 2");
+        assert_eq!(level_command(TimeControl { initial_ms: 90_000, increment_ms: 500 }), "level 0 1:30 0.5");
+
+        let limits = SearchLimits { wtime: Some(61_000), btime: Some(58_000), ..SearchLimits::default() };
+        assert_eq!(limit_commands(&limits, Color::Black, true), vec!["time 5800", "otim 6100"]);
+        assert!(limit_commands(&limits, Color::Black, false).is_empty());
+
+        let fixed = SearchLimits { movetime: Some(1_500), depth: Some(9), ..SearchLimits::default() };
+        assert_eq!(limit_commands(&fixed, Color::White, true), vec!["st 2", "sd 9"]);
+    }
+
+    #[test]
+    fn parses_thinking_output_and_san_moves() {
+        let pos = VariantPosition::default();
+        let info = parse_thinking("9 35 120 456000 1. e4 e5 2. Nf3 Nc6", &pos).expect("thinking line");
+        assert_eq!(info.depth, Some(9));
+        assert_eq!(info.score, Some(Score::Cp(35)));
+        assert_eq!(info.time_ms, Some(1200));
+        assert_eq!(info.pv, vec!["e2e4", "e7e5", "g1f3", "b8c6"]);
+
+        assert_eq!(parse_thinking("Illegal move: e2e5", &pos), None);
+        assert_eq!(move_to_uci("Nf3", &pos).as_deref(), Some("g1f3"));
+        assert_eq!(move_to_uci("e2e4", &pos).as_deref(), Some("e2e4"));
+    }
+
+    #[test]
+    fn maps_mate_scores() {
+        assert_eq!(xboard_score(-250), Score::Cp(-250));
+        assert_eq!(xboard_score(100_003), Score::Mate(3));
+        assert_eq!(xboard_score(-100_002), Score::Mate(-2));
+        assert_eq!(xboard_score(32_766), Score::Mate(1));
+        assert_eq!(xboard_score(-32_763), Score::Mate(-2));
+    }
+}